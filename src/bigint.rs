@@ -0,0 +1,463 @@
+/*
+ * Integers of any size, for arithmetic that would overflow an int
+ *
+ * A BigInt is a sign and a magnitude, with the magnitude held as base 2^32
+ * digits, least significant first. There are never any leading zero digits,
+ * so zero has no digits at all, and zero is never negative; every value has
+ * exactly one representation and == can compare the fields directly.
+ */
+
+pub struct BigInt {
+    negative: bool,
+    digits: ~[u32]
+}
+
+const base:float = 4294967296.0;
+
+pure fn trim( digits:~[u32] ) -> ~[u32] {
+    let mut length = digits.len();
+    while length > 0 && digits[length - 1] == 0 {
+        length -= 1;
+    }
+    vec::slice(digits, 0, length)
+}
+
+pure fn compare_magnitudes( a:&[u32], b:&[u32] ) -> int {
+    if a.len() != b.len() {
+        return if a.len() < b.len() { -1 } else { 1 };
+    }
+    let mut i = a.len();
+    while i > 0 {
+        i -= 1;
+        if a[i] != b[i] {
+            return if a[i] < b[i] { -1 } else { 1 };
+        }
+    }
+    0
+}
+
+pure fn add_magnitudes( a:&[u32], b:&[u32] ) -> ~[u32] {
+    let mut result:~[u32] = ~[];
+    let mut carry = 0u64;
+    for uint::range(0, uint::max(a.len(), b.len())) |i| {
+        let x = if i < a.len() { a[i] as u64 } else { 0 };
+        let y = if i < b.len() { b[i] as u64 } else { 0 };
+        let sum = x + y + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry > 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+// a - b, where a is at least as large as b
+pure fn sub_magnitudes( a:&[u32], b:&[u32] ) -> ~[u32] {
+    let mut result:~[u32] = ~[];
+    let mut borrow = 0u64;
+    for a.eachi() |i, &digit| {
+        let x = digit as u64;
+        let y = (if i < b.len() { b[i] as u64 } else { 0 }) + borrow;
+        if x >= y {
+            result.push((x - y) as u32);
+            borrow = 0;
+        } else {
+            result.push((x + (1u64 << 32) - y) as u32);
+            borrow = 1;
+        }
+    }
+    trim(result)
+}
+
+pure fn mul_magnitudes( a:&[u32], b:&[u32] ) -> ~[u32] {
+    if a.len() == 0 || b.len() == 0 {
+        return ~[];
+    }
+    let mut result = vec::from_elem(a.len() + b.len(), 0u32);
+    for a.eachi() |i, &x| {
+        let mut carry = 0u64;
+        for b.eachi() |j, &y| {
+            let product = (x as u64) * (y as u64) + (result[i + j] as u64) + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(result)
+}
+
+// the quotient and remainder of a by a single digit
+pure fn divide_by_digit( a:&[u32], divisor:u32 ) -> (~[u32], u32) {
+    let mut quotient = vec::from_elem(a.len(), 0u32);
+    let mut remainder = 0u64;
+    let mut i = a.len();
+    while i > 0 {
+        i -= 1;
+        let current = (remainder << 32) | (a[i] as u64);
+        quotient[i] = (current / (divisor as u64)) as u32;
+        remainder = current % (divisor as u64);
+    }
+    (trim(quotient), remainder as u32)
+}
+
+// long division a bit at a time, which is slow but simple and only needs the
+// operations above
+pure fn divide_magnitudes( a:&[u32], b:&[u32] ) -> (~[u32], ~[u32]) {
+    if compare_magnitudes(a, b) < 0 {
+        return (~[], vec::from_slice(a));
+    }
+    if b.len() == 1 {
+        let (quotient, remainder) = divide_by_digit(a, b[0]);
+        return (quotient, trim(~[remainder]));
+    }
+    let mut quotient = vec::from_elem(a.len(), 0u32);
+    let mut remainder:~[u32] = ~[];
+    let mut i = a.len() * 32;
+    while i > 0 {
+        i -= 1;
+        let bit = (a[i / 32] >> (i % 32)) & 1;
+        remainder = add_magnitudes(remainder, remainder);
+        if bit == 1 {
+            remainder = add_magnitudes(remainder, ~[1u32]);
+        }
+        if compare_magnitudes(remainder, b) >= 0 {
+            remainder = sub_magnitudes(remainder, b);
+            quotient[i / 32] |= 1u32 << (i % 32);
+        }
+    }
+    (trim(quotient), remainder)
+}
+
+pub impl BigInt {
+    static pure fn new( negative:bool, digits:~[u32] ) -> BigInt {
+        let digits = trim(digits);
+        BigInt { negative: negative && digits.len() > 0, digits: digits }
+    }
+
+    static pure fn from_int( n:int ) -> BigInt {
+        // the most negative int has no positive counterpart, so take one off first
+        let magnitude = if n < 0 { (-(n + 1)) as u64 + 1 } else { n as u64 };
+        BigInt::new(n < 0, ~[magnitude as u32, (magnitude >> 32) as u32])
+    }
+
+    // the integer part of a finite float; fails for infinities and NaN, which callers
+    // check for first
+    static pure fn from_float( f:float ) -> BigInt {
+        if float::is_NaN(f) || float::is_infinite(f) {
+            fail ~"BigInt from a float that is not finite";
+        }
+        let mut rest = float::floor(float::abs(f));
+        let mut digits:~[u32] = ~[];
+        while rest >= 1.0 {
+            let quotient = float::floor(rest / base);
+            digits.push((rest - quotient * base) as u32);
+            rest = quotient;
+        }
+        BigInt::new(f < 0.0, digits)
+    }
+
+    // None when the value is too large for an int
+    pure fn to_int(&self) -> Option<int> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+        let mut magnitude = 0u64;
+        for self.digits.eachi() |i, &digit| {
+            magnitude |= (digit as u64) << (32 * i);
+        }
+        let largest = int::max_value as u64;
+        if !self.negative && magnitude <= largest {
+            Some(magnitude as int)
+        } else if self.negative && magnitude <= largest + 1 {
+            Some(-((magnitude - 1) as int) - 1)
+        } else {
+            None
+        }
+    }
+
+    pure fn to_float(&self) -> float {
+        // adding in more than two digits would round more than once, so past 64 bits keep
+        // the top 63 and a sticky bit for whether any of the rest are set
+        let bits = self.bit_length();
+        if bits > 64 {
+            let shift = bits - 63;
+            let (top, rest) = self.divide(&BigInt::power_of_two(shift));
+            let sticky = if rest.is_zero() { 0 } else if rest.is_negative() { -1 } else { 1 };
+            let top = top * BigInt::from_int(2) + BigInt::from_int(sticky);
+            return top.to_float() * float::pow(2.0, (shift - 1) as float);
+        }
+        let mut result = 0.0;
+        let mut i = self.digits.len();
+        while i > 0 {
+            i -= 1;
+            result = result * base + (self.digits[i] as float);
+        }
+        if self.negative { -result } else { result }
+    }
+
+    pure fn is_zero(&self) -> bool {
+        self.digits.len() == 0
+    }
+
+    pure fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pure fn abs(&self) -> BigInt {
+        BigInt::new(false, copy self.digits)
+    }
+
+    // -1, 0 or 1 as self is less than, equal to or greater than other
+    pure fn compare(&self, other:&BigInt) -> int {
+        match (self.negative, other.negative) {
+            (false, true) => 1,
+            (true, false) => -1,
+            (false, false) => compare_magnitudes(self.digits, other.digits),
+            (true, true) => compare_magnitudes(other.digits, self.digits)
+        }
+    }
+
+    // the quotient rounded towards zero, and the remainder, which has the sign of self
+    pure fn divide(&self, other:&BigInt) -> (BigInt, BigInt) {
+        if other.is_zero() {
+            fail ~"BigInt division by zero";
+        }
+        let (quotient, remainder) = divide_magnitudes(self.digits, other.digits);
+        (BigInt::new(self.negative != other.negative, quotient), BigInt::new(self.negative, remainder))
+    }
+
+    // the largest integer dividing both, which is never negative
+    pure fn gcd(&self, other:&BigInt) -> BigInt {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let (_, remainder) = a.divide(&b);
+            a = b;
+            b = remainder;
+        }
+        a
+    }
+
+    // the number of bits in the magnitude, leaving out leading zeros
+    pure fn bit_length(&self) -> uint {
+        if self.is_zero() {
+            return 0;
+        }
+        let mut top = self.digits.last();
+        let mut bits = (self.digits.len() - 1) * 32;
+        while top > 0 {
+            top >>= 1;
+            bits += 1;
+        }
+        bits
+    }
+
+    // the largest integer whose square is no greater than self, by Newton's method from
+    // a power of two above the root; fails for negative numbers
+    pure fn sqrt(&self) -> BigInt {
+        if self.negative {
+            fail ~"BigInt square root of a negative number";
+        }
+        if self.is_zero() {
+            return BigInt::from_int(0);
+        }
+        let two = BigInt::from_int(2);
+        let mut root = BigInt::power_of_two((self.bit_length() + 1) / 2);
+        loop {
+            let next = (root + *self / root) / two;
+            if next >= root {
+                return root;
+            }
+            root = next;
+        }
+    }
+
+    static pure fn power_of_two( exponent:uint ) -> BigInt {
+        let mut digits = vec::from_elem(exponent / 32 + 1, 0u32);
+        digits[exponent / 32] = 1u32 << (exponent % 32);
+        BigInt::new(false, digits)
+    }
+
+    pure fn to_str(&self) -> ~str {
+        if self.is_zero() {
+            return ~"0";
+        }
+        // nine decimal digits at a time, least significant group first
+        let mut groups:~[u32] = ~[];
+        let mut rest = copy self.digits;
+        while rest.len() > 0 {
+            let (quotient, remainder) = divide_by_digit(rest, 1000000000);
+            groups.push(remainder);
+            rest = quotient;
+        }
+        let mut text = if self.negative { ~"-" } else { ~"" };
+        text += u32::str(groups.last());
+        let mut i = groups.len() - 1;
+        while i > 0 {
+            i -= 1;
+            let group = u32::str(groups[i]);
+            text += str::repeat("0", 9 - group.len()) + group;
+        }
+        text
+    }
+}
+
+pub impl BigInt : ops::Add<BigInt,BigInt> {
+    pure fn add(&self, other:&BigInt) -> BigInt {
+        if self.negative == other.negative {
+            BigInt::new(self.negative, add_magnitudes(self.digits, other.digits))
+        } else if compare_magnitudes(self.digits, other.digits) >= 0 {
+            BigInt::new(self.negative, sub_magnitudes(self.digits, other.digits))
+        } else {
+            BigInt::new(other.negative, sub_magnitudes(other.digits, self.digits))
+        }
+    }
+}
+
+pub impl BigInt : ops::Sub<BigInt,BigInt> {
+    pure fn sub(&self, other:&BigInt) -> BigInt {
+        self.add(&-*other)
+    }
+}
+
+pub impl BigInt : ops::Mul<BigInt,BigInt> {
+    pure fn mul(&self, other:&BigInt) -> BigInt {
+        BigInt::new(self.negative != other.negative, mul_magnitudes(self.digits, other.digits))
+    }
+}
+
+pub impl BigInt : ops::Div<BigInt,BigInt> {
+    pure fn div(&self, other:&BigInt) -> BigInt {
+        self.divide(other).first()
+    }
+}
+
+pub impl BigInt : ops::Neg<BigInt> {
+    pure fn neg(&self) -> BigInt {
+        BigInt::new(!self.negative, copy self.digits)
+    }
+}
+
+pub impl BigInt : cmp::Eq {
+    pure fn eq(&self, other:&BigInt) -> bool {
+        self.negative == other.negative && self.digits == other.digits
+    }
+
+    pure fn ne(&self, other:&BigInt) -> bool {
+        !self.eq(other)
+    }
+}
+
+pub impl BigInt : cmp::Ord {
+    pure fn lt(&self, other:&BigInt) -> bool { self.compare(other) < 0 }
+    pure fn le(&self, other:&BigInt) -> bool { self.compare(other) <= 0 }
+    pure fn gt(&self, other:&BigInt) -> bool { self.compare(other) > 0 }
+    pure fn ge(&self, other:&BigInt) -> bool { self.compare(other) >= 0 }
+}
+
+fn big( text:&str ) -> BigInt {
+    // builds the number a decimal digit at a time, which also exercises add and mul
+    let ten = BigInt::from_int(10);
+    let mut result = BigInt::from_int(0);
+    for str::each_char(text) |c| {
+        if c != '-' {
+            result = result * ten + BigInt::from_int(char::to_digit(c, 10).get() as int);
+        }
+    }
+    if str::starts_with(text, "-") { -result } else { result }
+}
+
+#[test]
+fn test_that_ints_convert_both_ways() {
+    for [0, 1, -1, 4294967296, -4294967297, int::max_value, int::min_value].each |&n| {
+        assert BigInt::from_int(n).to_int() == Some(n);
+    }
+    assert (BigInt::from_int(int::max_value) + BigInt::from_int(1)).to_int().is_none();
+    assert (BigInt::from_int(int::min_value) - BigInt::from_int(1)).to_int().is_none();
+}
+
+#[test]
+fn test_that_zero_has_one_representation() {
+    assert BigInt::from_int(5) - BigInt::from_int(5) == BigInt::from_int(0);
+    assert -BigInt::from_int(0) == BigInt::from_int(0);
+    assert !(BigInt::from_int(-3) * BigInt::from_int(0)).is_negative();
+}
+
+#[test]
+fn test_that_big_integers_print_in_decimal() {
+    assert BigInt::from_int(0).to_str() == ~"0";
+    assert BigInt::from_int(-42).to_str() == ~"-42";
+    assert big(~"1000000000000000000000").to_str() == ~"1000000000000000000000";
+    assert big(~"-99999999999999999999999").to_str() == ~"-99999999999999999999999";
+}
+
+#[test]
+fn test_that_arithmetic_carries_between_digits() {
+    assert big(~"99999999999") * big(~"99999999999") == big(~"9999999999800000000001");
+    assert big(~"18446744073709551616") - BigInt::from_int(1) == big(~"18446744073709551615");
+    assert BigInt::from_int(-7) + big(~"100000000000000000000") == big(~"99999999999999999993");
+}
+
+#[test]
+fn test_that_division_truncates_towards_zero() {
+    let (quotient, remainder) = big(~"100000000000000000007").divide(&big(~"-10000000000"));
+    assert quotient == big(~"-10000000000");
+    assert remainder == BigInt::from_int(7);
+    let (quotient, remainder) = BigInt::from_int(-7).divide(&BigInt::from_int(2));
+    assert quotient == BigInt::from_int(-3) && remainder == BigInt::from_int(-1);
+}
+
+#[test]
+fn test_that_big_integers_are_ordered() {
+    assert big(~"-100000000000000000000") < BigInt::from_int(-1);
+    assert big(~"100000000000000000000") > big(~"99999999999999999999");
+    assert BigInt::from_int(3) <= BigInt::from_int(3);
+}
+
+#[test]
+fn test_that_gcd_ignores_signs() {
+    assert BigInt::from_int(12).gcd(&BigInt::from_int(-18)) == BigInt::from_int(6);
+    assert BigInt::from_int(0).gcd(&BigInt::from_int(-5)) == BigInt::from_int(5);
+    assert big(~"100000000000000000000").gcd(&big(~"300000000000000000007")) == BigInt::from_int(1);
+}
+
+#[test]
+fn test_that_bit_lengths_count_significant_bits() {
+    assert BigInt::from_int(0).bit_length() == 0;
+    assert BigInt::from_int(-5).bit_length() == 3;
+    assert BigInt::power_of_two(70).bit_length() == 71;
+    assert BigInt::power_of_two(64) == big(~"18446744073709551616");
+}
+
+#[test]
+fn test_that_floats_convert_both_ways() {
+    assert BigInt::from_float(1e20) == big(~"100000000000000000000");
+    assert BigInt::from_float(-2.5) == BigInt::from_int(-2);
+    assert big(~"100000000000000000000").to_float() == 1e20;
+}
+
+#[test]
+fn test_that_wide_numbers_round_to_the_nearest_float() {
+    // 2^120 + 2^67 + 1 is just past halfway to the next float up, which a digit at a time misses
+    let wide = BigInt::power_of_two(120) + BigInt::power_of_two(67) + BigInt::from_int(1);
+    assert wide.to_float() == float::pow(2.0, 120.0) + float::pow(2.0, 68.0);
+    assert (-wide).to_float() == -float::pow(2.0, 120.0) - float::pow(2.0, 68.0);
+}
+
+#[test]
+fn test_that_square_roots_round_down() {
+    assert BigInt::from_int(0).sqrt() == BigInt::from_int(0);
+    assert BigInt::from_int(1).sqrt() == BigInt::from_int(1);
+    assert BigInt::from_int(15).sqrt() == BigInt::from_int(3);
+    assert BigInt::from_int(16).sqrt() == BigInt::from_int(4);
+    let root = BigInt::power_of_two(80) + BigInt::from_int(1);
+    assert (root * root).sqrt() == root;
+    assert (root * root - BigInt::from_int(1)).sqrt() == root - BigInt::from_int(1);
+}
+
+#[test]
+#[should_fail]
+fn test_that_infinity_is_not_an_integer() {
+    BigInt::from_float(float::infinity);
+}
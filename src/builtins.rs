@@ -1,14 +1,42 @@
 /*
  * Built-in method definitions for scheme
  * 
- * +, -, *, / (works on lists of numbers)
- * =, >, <, >=, <= (works on list of numbers)
+ * +, -, *, / (works on lists of numbers, with integers of any size; / of integers is exact)
+ * numerator, denominator, exact, inexact, rationalize
+ * exact?, inexact?, exact->inexact, inexact->exact
+ * number?, complex?, real?, rational?, integer?
+ * make-rectangular, make-polar, real-part, imag-part, magnitude, angle
+ * sqrt (of a negative number or a complex one gives a complex root)
+ * = (works on list of numbers, comparing exact and inexact ones by value)
+ * >, <, >=, <= (works on list of real numbers)
  * not (works on single argument)
  * list (works on any arguments)
- * list?, null?, symbol? (works on single argument)
- * cons, car, cdr, append
+ * list?, null?, pair?, symbol? (works on single argument)
+ * cons, car, cdr, append (cons and append build dotted pairs for non-list tails)
+ * set-car!, set-cdr!
  * equal?
  * eq?
+ * string?, string-length, string-append, substring, string=?
+ * string->symbol, symbol->string
+ * display, write, write-simple, newline (write labels shared vectors, write-simple does not)
+ * pretty-print (writes over several lines to fit an optional width)
+ * char?, char->integer, integer->char
+ * char-upcase, char-downcase (for the Latin, Greek and Cyrillic alphabets)
+ * char-alphabetic?, char-numeric?, char-whitespace?
+ * char-upper-case?, char-lower-case?
+ * char=?, char<?, char>?, char<=?, char>=? (works on lists of characters)
+ * vector?, vector, make-vector, vector-ref, vector-set!, vector-length
+ * vector->list, list->vector, vector-fill!, vector-copy
+ * vector-map, vector-for-each (work on any number of vectors)
+ * bytevector?, bytevector, make-bytevector, bytevector-length
+ * bytevector-u8-ref, bytevector-u8-set!, bytevector-copy, bytevector-append
+ * utf8->string, string->utf8
+ * make-hash-table (keyed by equal?, eqv?, eq? or string=?, equal? by default)
+ * hash-table?, hash-table-size, hash-table-contains?
+ * hash-table-ref, hash-table-ref/default, hash-table-set!, hash-table-delete!
+ * hash-table-update!, hash-table-keys, hash-table->alist, hash-table-walk
+ * error (makes a condition from a message and irritants)
+ * error-object?, error-object-message, error-object-irritants, file-error?, read-error?
  *
  */
 
@@ -50,181 +78,1345 @@ fn sometimes_ess( number:uint ) -> ~str {
 macro_rules! assert_mininum_number_of_args {
     ($function:expr $minimum:expr) => {
         if args.len() < $minimum {
-            return Error(fmt!("Built-in function '%s' takes at least %u argument%s. It was called with %u '%s'", $function, $minimum, sometimes_ess($minimum), args.len(), List(args).to_str()));
+            return new_error( ArityCondition, fmt!("Built-in function '%s' takes at least %u argument%s", $function, $minimum, sometimes_ess($minimum)), copy args );
         };
     }
 }
 
-macro_rules! ensure_arguments_are_numbers {
-    () => {
+macro_rules! ensure_arguments_are {
+    ($accepts:ident $kind:expr) => {
         for args.each() |&arg| {
-            if !arg.is_number() {
-                return Error( fmt!("%s was given where a number was expected", arg.to_str()) );
+            if !arg.$accepts() {
+                return new_error( TypeCondition, fmt!("Expected %s", $kind), ~[copy arg] );
+            }
+        };
+    }
+}
+
+macro_rules! comparison_function {
+    ($function:ident $method:ident $name:expr $accepts:ident $kind:expr) => {
+        pub fn $function( args:~[Expression] ) -> Expression {
+            assert_mininum_number_of_args!($name 2)
+            return_first_error!()
+            ensure_arguments_are!($accepts $kind)
+
+            let comparisons = vec::map2( args.init(), args.tail(),
+                                         |a, b| {a.$method(b)});
+            Bool(vec::foldl(true, comparisons, |x, &y| {x && y}))
+        }
+    }
+}
+
+comparison_function!(lt lt ~"<" is_real ~"a real number")
+comparison_function!(le le ~"<=" is_real ~"a real number")
+comparison_function!(gt gt ~">" is_real ~"a real number")
+comparison_function!(ge ge ~">=" is_real ~"a real number")
+comparison_function!(equals numeric_eq ~"=" is_number ~"a number")
+
+// the number as an Int, Big or Rational, or as a Float when inexact is set
+fn with_exactness( fraction:Fraction, inexact:bool ) -> Expression {
+    if inexact {
+        Float(fraction.to_float())
+    } else {
+        Expression::from_fraction(fraction)
+    }
+}
+
+macro_rules! assert_arg_count_range {
+    ($function:expr $minimum:expr $maximum:expr) => {
+        if args.len() > $maximum || args.len() < $minimum {
+            let head = fmt!("Built-in function '%s' takes", $function);
+            let tail = if $minimum == $maximum {
+                fmt!("only %u argument%s", $minimum, sometimes_ess($minimum))
+            } else {
+                fmt!("betwen %u and %u arguments", $minimum, $maximum)
+            };
+
+            return new_error( ArityCondition, fmt!("%s %s", head, tail), copy args );
+        }
+    }
+}
+
+pub fn not( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"not" 1 1 )
+
+    return Bool(!args[0].to_bool());
+}
+
+pub fn numerator( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"numerator" 1 1 )
+
+    match args[0].to_exact_fraction() {
+        Some(fraction) => with_exactness( Fraction::from_integer(copy fraction.numerator), !args[0].is_exact() ),
+        None => new_error( TypeCondition, ~"Built-in function 'numerator' requires a rational number", ~[copy args[0]] )
+    }
+}
+
+pub fn denominator( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"denominator" 1 1 )
+
+    match args[0].to_exact_fraction() {
+        Some(fraction) => with_exactness( Fraction::from_integer(copy fraction.denominator), !args[0].is_exact() ),
+        None => new_error( TypeCondition, ~"Built-in function 'denominator' requires a rational number", ~[copy args[0]] )
+    }
+}
+
+pub fn exact( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"exact" 1 1 )
+
+    match args[0].to_exact_fraction() {
+        Some(fraction) => Expression::from_fraction(fraction),
+        None => new_error( TypeCondition, ~"Built-in function 'exact' requires a finite real number", ~[copy args[0]] )
+    }
+}
+
+pub fn inexact( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"inexact" 1 1 )
+
+    if args[0].is_real() {
+        Float(args[0].to_float())
+    } else if args[0].is_number() {
+        copy args[0]
+    } else {
+        new_error( TypeCondition, ~"Built-in function 'inexact' requires a number", ~[copy args[0]] )
+    }
+}
+
+// the simplest rational within the second argument of the first
+pub fn rationalize( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"rationalize" 2 2 )
+
+    match (args[0].to_exact_fraction(), args[1].to_exact_fraction()) {
+        (Some(x), Some(y)) => {
+            let y = if y.numerator.is_negative() { -y } else { y };
+            let simplest = simplest_between( &(x - y), &(x + y) );
+            with_exactness( simplest, !args[0].is_exact() || !args[1].is_exact() )
+        }
+        _ => new_error( TypeCondition, ~"Built-in function 'rationalize' requires finite numbers", copy args )
+    }
+}
+
+pub fn exact_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"exact?" 1 1 )
+    ensure_arguments_are!(is_number ~"a number")
+
+    Bool(args[0].is_exact())
+}
+
+pub fn inexact_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"inexact?" 1 1 )
+    ensure_arguments_are!(is_number ~"a number")
+
+    Bool(!args[0].is_exact())
+}
+
+pub fn number_( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"number?" 1 1 )
+
+    Bool(args[0].is_number())
+}
+
+pub fn real_( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"real?" 1 1 )
+
+    Bool(args[0].is_real())
+}
+
+pub fn rational_( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"rational?" 1 1 )
+
+    Bool(args[0].to_exact_fraction().is_some())
+}
+
+pub fn integer_( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"integer?" 1 1 )
+
+    match args[0].to_exact_fraction() {
+        Some(fraction) => Bool(fraction.is_integer()),
+        None => Bool(false)
+    }
+}
+
+pub fn make_rectangular( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"make-rectangular" 2 2 )
+    ensure_arguments_are!(is_real ~"a real number")
+
+    Expression::new_rectangular( &args[0], &args[1] )
+}
+
+pub fn make_polar( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"make-polar" 2 2 )
+    ensure_arguments_are!(is_real ~"a real number")
+
+    Expression::new_polar( &args[0], &args[1] )
+}
+
+pub fn real_part( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"real-part" 1 1 )
+    ensure_arguments_are!(is_number ~"a number")
+
+    match args[0] {
+        Complex(real, _) => Float(real),
+        _ => copy args[0]
+    }
+}
+
+pub fn imag_part( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"imag-part" 1 1 )
+    ensure_arguments_are!(is_number ~"a number")
+
+    match args[0] {
+        Complex(_, imaginary) => Float(imaginary),
+        _ => Int(0)
+    }
+}
+
+pub fn magnitude( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"magnitude" 1 1 )
+    ensure_arguments_are!(is_number ~"a number")
+
+    match args[0] {
+        Complex(real, imaginary) => Float(float::hypot(real, imaginary)),
+        _ => if args[0] < Int(0) { Int(0) - args[0] } else { copy args[0] }
+    }
+}
+
+pub fn angle( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"angle" 1 1 )
+    ensure_arguments_are!(is_number ~"a number")
+
+    match args[0] {
+        Complex(real, imaginary) => Float(float::atan2(imaginary, real)),
+        _ => if args[0].is_exact() && args[0] >= Int(0) {
+            Int(0)
+        } else {
+            Float(float::atan2(0.0, args[0].to_float()))
+        }
+    }
+}
+
+// the root of an exact square
+fn exact_root( n:&BigInt ) -> Option<BigInt> {
+    let root = n.sqrt();
+    if root * root == *n { Some(root) } else { None }
+}
+
+// the principal square root, which is exact for exact squares
+pub fn sqrt( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"sqrt" 1 1 )
+    ensure_arguments_are!(is_number ~"a number")
+
+    match args[0].to_fraction() {
+        Some(fraction) => if !fraction.numerator.is_negative() {
+            match (exact_root(&fraction.numerator), exact_root(&fraction.denominator)) {
+                (Some(n), Some(d)) => return Expression::from_fraction(Fraction::new(n, d)),
+                _ => ()
+            }
+        },
+        None => ()
+    }
+    let (real, imaginary) = args[0].to_complex();
+    if imaginary == 0.0 {
+        if real < 0.0 {
+            Expression::new_complex(0.0, float::sqrt(-real))
+        } else {
+            Float(float::sqrt(real))
+        }
+    } else {
+        // half way round to the number from the positive real line, on the side it is on
+        let magnitude = float::hypot(real, imaginary);
+        let root_imaginary = float::sqrt((magnitude - real) / 2.0);
+        Expression::new_complex(float::sqrt((magnitude + real) / 2.0),
+                                if imaginary < 0.0 { -root_imaginary } else { root_imaginary })
+    }
+}
+
+pub fn car( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"car" 1 1 )
+
+    match copy args[0] {
+        Pair(cell) => copy cell.car,
+        _ => new_error( TypeCondition, ~"Built-in function 'car' requires a non-empty list or pair argument", ~[copy args[0]] )
+    }
+}
+
+pub fn cdr( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"cdr" 1 1 )
+
+    match copy args[0] {
+        Pair(cell) => copy cell.cdr,
+        _ => new_error( TypeCondition, ~"Built-in function 'cdr' requires a non-empty list or pair argument", ~[copy args[0]] )
+    }
+}
+
+pub fn cons( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"cons" 2 2 )
+
+    new_pair( copy args[0], copy args[1] )
+}
+
+pub fn set_car( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"set-car!" 2 2 )
+
+    match copy args[0] {
+        Pair(cell) => {
+            cell.car = copy args[1];
+            Unspecified
+        }
+        _ => new_error( TypeCondition, ~"Built-in function 'set-car!' requires a pair argument", ~[copy args[0]] )
+    }
+}
+
+pub fn set_cdr( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"set-cdr!" 2 2 )
+
+    match copy args[0] {
+        Pair(cell) => {
+            cell.cdr = copy args[1];
+            Unspecified
+        }
+        _ => new_error( TypeCondition, ~"Built-in function 'set-cdr!' requires a pair argument", ~[copy args[0]] )
+    }
+}
+
+pub fn append( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"append" 2 2 )
+
+    // the last argument becomes the tail without being copied
+    match args[0].list_items() {
+        Some(list1) => {
+            new_dotted( list1, copy args[1] )
+        }
+        None => new_error( TypeCondition, ~"Built-in function 'append' requires a proper list as the first arguments", copy args )
+    }
+}
+
+pub fn length( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"length" 1 1 )
+    
+    match args[0].list_items() {
+        Some(list) => Int(list.len() as int),
+        None => new_error( TypeCondition, ~"Built-in function 'length' requires a proper list argument", copy args )
+    }
+}
+
+pub fn equal_( args:~[Expression] ) -> Expression {
+    // conditions are compared like any other value
+    assert_arg_count_range!( ~"equal?" 2 2 )
+    
+    Bool(args[0] == args[1])
+}
+
+
+pub fn symbol_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"symbol?" 1 1 )
+    
+    match args[0] {
+        Symbol(_) => Bool(true),
+        _ => Bool(false)
+    }
+}
+
+pub fn list_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"list?" 1 1 )
+    
+    Bool(args[0].is_list())
+}
+
+pub fn pair_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"pair?" 1 1 )
+
+    match args[0] {
+        Pair(_) => Bool(true),
+        _ => Bool(false)
+    }
+}
+
+pub fn null_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"null?" 1 1 )
+    
+    match args[0] {
+        Nil => Bool(true),
+        _ => Bool(false)
+    }
+}
+
+pub fn string_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"string?" 1 1 )
+
+    match args[0] {
+        String(_) => Bool(true),
+        _ => Bool(false)
+    }
+}
+
+pub fn string_length( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"string-length" 1 1 )
+
+    match copy args[0] {
+        String(string) => Int(str::char_len(string) as int),
+        _ => new_error( TypeCondition, ~"Built-in function 'string-length' requires a string argument", ~[copy args[0]] )
+    }
+}
+
+pub fn string_append( args:~[Expression]) -> Expression {
+    return_first_error!()
+
+    let mut result = ~"";
+    for args.each() |&arg| {
+        match arg {
+            String(string) => str::push_str(&mut result, string),
+            _ => return new_error( TypeCondition, ~"Built-in function 'string-append' requires string arguments", copy args )
+        }
+    }
+    String(result)
+}
+
+pub fn substring( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"substring" 2 3 )
+
+    match copy args {
+        [String(string), Int(start)] => substring( ~[String(copy string), Int(start), Int(str::char_len(string) as int)] ),
+        [String(string), Int(start), Int(end)] => {
+            let chars = str::chars(string);
+            if start < 0 || end < start || end as uint > chars.len() {
+                new_error( TypeCondition, ~"Built-in function 'substring' was given indices outside of the string", copy args )
+            } else {
+                String(str::from_chars(vec::slice(chars, start as uint, end as uint)))
+            }
+        }
+        _ => new_error( TypeCondition, ~"Built-in function 'substring' requires a string and integer indices", copy args )
+    }
+}
+
+pub fn string_equals( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_mininum_number_of_args!(~"string=?" 1)
+
+    for args.each() |&arg| {
+        match arg {
+            String(_) => (),
+            _ => return new_error( TypeCondition, ~"Built-in function 'string=?' requires string arguments", copy args )
+        }
+    }
+    for args.tail().each() |&expr| {
+        if expr != args.head() {
+            return Bool(false);
+        }
+    }
+    return Bool(true);
+}
+
+pub fn string_to_symbol( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"string->symbol" 1 1 )
+
+    match copy args[0] {
+        String(string) => Symbol(string),
+        _ => new_error( TypeCondition, ~"Built-in function 'string->symbol' requires a string argument", ~[copy args[0]] )
+    }
+}
+
+pub fn symbol_to_string( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"symbol->string" 1 1 )
+
+    match copy args[0] {
+        Symbol(string) => String(string),
+        _ => new_error( TypeCondition, ~"Built-in function 'symbol->string' requires a symbol argument", ~[copy args[0]] )
+    }
+}
+
+pub fn display( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"display" 1 1 )
+
+    io::print(args[0].to_display_str());
+    Unspecified
+}
+
+pub fn write( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"write" 1 1 )
+
+    io::print(args[0].to_str());
+    Unspecified
+}
+
+pub fn write_simple( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"write-simple" 1 1 )
+
+    io::print(args[0].to_simple_str());
+    Unspecified
+}
+
+pub fn pretty_print( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"pretty-print" 1 2 )
+
+    let width = if args.len() == 2 {
+        match args[1] {
+            Int(width) if width > 0 => width as uint,
+            _ => return new_error( TypeCondition, ~"Built-in function 'pretty-print' requires a positive width", ~[copy args[1]] )
+        }
+    } else {
+        pretty::default_width
+    };
+    io::println(pretty::pretty(&args[0], width));
+    Unspecified
+}
+
+pub fn newline( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"newline" 0 0 )
+
+    io::print(~"\n");
+    Unspecified
+}
+
+macro_rules! char_predicate {
+    ($function:ident $name:expr $predicate:expr) => {
+        pub fn $function( args:~[Expression]) -> Expression {
+            return_first_error!()
+            assert_arg_count_range!( $name 1 1 )
+
+            match args[0] {
+                Char(c) => Bool($predicate(c)),
+                _ => new_error( TypeCondition, fmt!("Built-in function '%s' requires a character argument", $name), ~[copy args[0]] )
+            }
+        }
+    }
+}
+
+char_predicate!(char_alphabetic_ ~"char-alphabetic?" char::is_alphabetic)
+char_predicate!(char_numeric_ ~"char-numeric?" char::is_digit)
+char_predicate!(char_whitespace_ ~"char-whitespace?" char::is_whitespace)
+char_predicate!(char_upper_case_ ~"char-upper-case?" char::is_uppercase)
+char_predicate!(char_lower_case_ ~"char-lower-case?" char::is_lowercase)
+
+macro_rules! char_comparison_function {
+    ($function:ident $name:expr $comparison:ident) => {
+        pub fn $function( args:~[Expression] ) -> Expression {
+            assert_mininum_number_of_args!($name 2)
+            return_first_error!()
+
+            let mut codes:~[uint] = ~[];
+            for args.each() |&arg| {
+                match arg {
+                    Char(c) => codes.push(c as uint),
+                    _ => return new_error( TypeCondition, fmt!("Built-in function '%s' requires character arguments", $name), copy args )
+                }
+            }
+            let comparisons = vec::map2( codes.init(), codes.tail(),
+                                         |a, b| {a.$comparison(b)});
+            Bool(vec::foldl(true, comparisons, |x, &y| {x && y}))
+        }
+    }
+}
+
+char_comparison_function!(char_eq ~"char=?" eq)
+char_comparison_function!(char_lt ~"char<?" lt)
+char_comparison_function!(char_le ~"char<=?" le)
+char_comparison_function!(char_gt ~"char>?" gt)
+char_comparison_function!(char_ge ~"char>=?" ge)
+
+pub fn char_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"char?" 1 1 )
+
+    match args[0] {
+        Char(_) => Bool(true),
+        _ => Bool(false)
+    }
+}
+
+pub fn char_to_integer( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"char->integer" 1 1 )
+
+    match args[0] {
+        Char(c) => Int(c as int),
+        _ => new_error( TypeCondition, ~"Built-in function 'char->integer' requires a character argument", ~[copy args[0]] )
+    }
+}
+
+pub fn integer_to_char( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"integer->char" 1 1 )
+
+    match args[0] {
+        Int(n) if n >= 0 && n <= 0x10FFFF && (n < 0xD800 || n > 0xDFFF) => Char(n as char),
+        _ => new_error( TypeCondition, ~"Built-in function 'integer->char' requires a Unicode scalar value", ~[copy args[0]] )
+    }
+}
+
+// case mapping covers the Latin, Greek and Cyrillic alphabets; anything else maps to itself
+
+// runs of lower case letters whose upper case letters are a fixed distance below them,
+// as (first, last, distance)
+fn case_distances() -> ~[(uint, uint, uint)] {
+    ~[(0x61, 0x7A, 32), (0xE0, 0xFE, 32), (0x3B1, 0x3C1, 32), (0x3C3, 0x3CB, 32),
+      (0x430, 0x44F, 32), (0x450, 0x45F, 80)]
+}
+
+// runs in Latin Extended-A where each upper case letter is followed by its lower case one
+fn case_pairs() -> ~[(uint, uint)] {
+    ~[(0x100, 0x12F), (0x132, 0x137), (0x139, 0x148), (0x14A, 0x177), (0x179, 0x17E)]
+}
+
+fn upcase( c:char ) -> char {
+    let code = c as uint;
+    // the division sign sits among the Latin-1 lower case letters
+    if code == 0xF7 {
+        return c;
+    }
+    for case_distances().each() |&(first, last, distance)| {
+        if code >= first && code <= last {
+            return (code - distance) as char;
+        }
+    }
+    for case_pairs().each() |&(first, last)| {
+        if code >= first && code <= last && (code - first) % 2 == 1 {
+            return (code - 1) as char;
+        }
+    }
+    match code {
+        0xFF => 0x178 as char,
+        // final sigma
+        0x3C2 => 0x3A3 as char,
+        _ => c
+    }
+}
+
+fn downcase( c:char ) -> char {
+    let code = c as uint;
+    // the multiplication sign sits among the Latin-1 upper case letters
+    if code == 0xD7 {
+        return c;
+    }
+    for case_distances().each() |&(first, last, distance)| {
+        if code >= first - distance && code <= last - distance {
+            return (code + distance) as char;
+        }
+    }
+    for case_pairs().each() |&(first, last)| {
+        if code >= first && code < last && (code - first) % 2 == 0 {
+            return (code + 1) as char;
+        }
+    }
+    match code {
+        0x178 => 0xFF as char,
+        _ => c
+    }
+}
+
+pub fn char_upcase( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"char-upcase" 1 1 )
+
+    match args[0] {
+        Char(c) => Char(upcase(c)),
+        _ => new_error( TypeCondition, ~"Built-in function 'char-upcase' requires a character argument", ~[copy args[0]] )
+    }
+}
+
+pub fn char_downcase( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"char-downcase" 1 1 )
+
+    match args[0] {
+        Char(c) => Char(downcase(c)),
+        _ => new_error( TypeCondition, ~"Built-in function 'char-downcase' requires a character argument", ~[copy args[0]] )
+    }
+}
+
+// the optional [start [end]] arguments found at args[first], defaulting to the whole sequence
+fn optional_range( name:&str, args:&[Expression], first:uint, length:uint ) -> Result<(uint, uint), Expression> {
+    let mut bounds = ~[0, length as int];
+    for uint::range(0, 2) |i| {
+        if args.len() > first + i {
+            match args[first + i] {
+                Int(n) => bounds[i] = n,
+                _ => return Err(new_error( TypeCondition, fmt!("Built-in function '%s' requires integer indices", name), ~[copy args[first + i]] ))
+            }
+        }
+    }
+    let (start, end) = (bounds[0], bounds[1]);
+    if start < 0 || end < start || end > length as int {
+        Err(new_error( TypeCondition, fmt!("Built-in function '%s' was given a range outside of 0 to %u", name, length), ~[Int(start), Int(end)] ))
+    } else {
+        Ok((start as uint, end as uint))
+    }
+}
+
+fn vector_index( name:&str, items:@mut ~[Expression], index:&Expression ) -> Result<uint, Expression> {
+    match *index {
+        Int(k) if k >= 0 && (k as uint) < items.len() => Ok(k as uint),
+        Int(k) => Err(new_error( TypeCondition, fmt!("Built-in function '%s' was given an index outside of 0 to %u", name, items.len()), ~[Int(k)] )),
+        _ => Err(new_error( TypeCondition, fmt!("Built-in function '%s' requires an integer index", name), ~[copy *index] ))
+    }
+}
+
+pub fn vector_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"vector?" 1 1 )
+
+    match args[0] {
+        Vector(_) => Bool(true),
+        _ => Bool(false)
+    }
+}
+
+pub fn vector( args:~[Expression]) -> Expression {
+    return_first_error!()
+    Vector(@mut args)
+}
+
+pub fn make_vector( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"make-vector" 1 2 )
+
+    let fill = if args.len() == 2 { copy args[1] } else { Int(0) };
+    match args[0] {
+        Int(k) if k >= 0 => Vector(@mut vec::from_elem(k as uint, fill)),
+        _ => new_error( TypeCondition, ~"Built-in function 'make-vector' requires a non-negative length", ~[copy args[0]] )
+    }
+}
+
+pub fn vector_length( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"vector-length" 1 1 )
+
+    match copy args[0] {
+        Vector(items) => Int(items.len() as int),
+        _ => new_error( TypeCondition, ~"Built-in function 'vector-length' requires a vector argument", ~[copy args[0]] )
+    }
+}
+
+pub fn vector_ref( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"vector-ref" 2 2 )
+
+    match copy args[0] {
+        Vector(items) => match vector_index( ~"vector-ref", items, &args[1] ) {
+            Ok(k) => copy (*items)[k],
+            Err(error) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'vector-ref' requires a vector argument", ~[copy args[0]] )
+    }
+}
+
+pub fn vector_set( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"vector-set!" 3 3 )
+
+    match copy args[0] {
+        Vector(items) => match vector_index( ~"vector-set!", items, &args[1] ) {
+            Ok(k) => {
+                (*items)[k] = copy args[2];
+                Unspecified
             }
-        };
+            Err(error) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'vector-set!' requires a vector argument", ~[copy args[0]] )
     }
 }
 
-macro_rules! comparison_function {
-    ($function:ident $name:expr) => {
-        pub fn $function( args:~[Expression] ) -> Expression {
-            assert_mininum_number_of_args!($name 2)
-            return_first_error!()
-            ensure_arguments_are_numbers!()
+pub fn vector_to_list( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"vector->list" 1 3 )
 
-            let comparisons = vec::map2( args.init(), args.tail(),
-                                         |a, b| {a.$function(b)});
-            Bool(vec::foldl(true, comparisons, |x, &y| {x && y}))
+    match copy args[0] {
+        Vector(items) => match optional_range( ~"vector->list", args, 1, items.len() ) {
+            Ok((start, end)) => new_list(vec::slice(*items, start, end)),
+            Err(error) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'vector->list' requires a vector argument", ~[copy args[0]] )
+    }
+}
+
+pub fn list_to_vector( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"list->vector" 1 1 )
+
+    match args[0].list_items() {
+        Some(items) => Vector(@mut items),
+        None => new_error( TypeCondition, ~"Built-in function 'list->vector' requires a proper list argument", ~[copy args[0]] )
+    }
+}
+
+pub fn vector_fill( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"vector-fill!" 2 4 )
+
+    match copy args[0] {
+        Vector(items) => match optional_range( ~"vector-fill!", args, 2, items.len() ) {
+            Ok((start, end)) => {
+                for uint::range(start, end) |k| {
+                    (*items)[k] = copy args[1];
+                }
+                Unspecified
+            }
+            Err(error) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'vector-fill!' requires a vector argument", ~[copy args[0]] )
+    }
+}
+
+pub fn vector_copy( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"vector-copy" 1 3 )
+
+    match copy args[0] {
+        Vector(items) => match optional_range( ~"vector-copy", args, 1, items.len() ) {
+            Ok((start, end)) => Vector(@mut vec::slice(*items, start, end)),
+            Err(error) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'vector-copy' requires a vector argument", ~[copy args[0]] )
+    }
+}
+
+// applies args[0] to the elements at each index of the vectors args[1..], stopping at the shortest
+fn map_vectors( name:&str, args:~[Expression] ) -> Result<~[Expression], Expression> {
+    let mut vectors:~[@mut ~[Expression]] = ~[];
+    for args.tail().each() |&arg| {
+        match arg {
+            Vector(items) => vectors.push(items),
+            _ => return Err(new_error( TypeCondition, fmt!("Built-in function '%s' requires vector arguments", name), ~[copy arg] ))
+        }
+    }
+    let length = vec::foldl(uint::max_value, vectors, |shortest, &items| { uint::min(shortest, items.len()) });
+    let mut results:~[Expression] = ~[];
+    for uint::range(0, length) |k| {
+        let result = apply( copy args[0], vectors.map(|&items| copy (*items)[k]) );
+        if result.is_error() {
+            return Err(result);
         }
+        results.push(result);
     }
+    Ok(results)
 }
 
-comparison_function!(lt ~"<")
-comparison_function!(le ~"<=")
-comparison_function!(gt ~">")
-comparison_function!(ge ~">=")
+pub fn vector_map( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_mininum_number_of_args!(~"vector-map" 2)
+
+    match map_vectors( ~"vector-map", args ) {
+        Ok(results) => Vector(@mut results),
+        Err(error) => error
+    }
+}
 
-pub fn equals( args:~[Expression]) -> Expression {
+pub fn vector_for_each( args:~[Expression]) -> Expression {
     return_first_error!()
+    assert_mininum_number_of_args!(~"vector-for-each" 2)
 
-    for args.tail().each() |&expr| {
-        if expr != args.head() {
-            return Bool(false);
+    match map_vectors( ~"vector-for-each", args ) {
+        Ok(_) => Unspecified,
+        Err(error) => error
+    }
+}
+
+fn byte( name:&str, arg:&Expression ) -> Result<u8, Expression> {
+    match *arg {
+        Int(n) if n >= 0 && n <= 255 => Ok(n as u8),
+        _ => Err(new_error( TypeCondition, fmt!("Built-in function '%s' requires byte values from 0 to 255", name), ~[copy *arg] ))
+    }
+}
+
+pub fn bytevector_( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"bytevector?" 1 1 )
+
+    match args[0] {
+        Bytevector(_) => Bool(true),
+        _ => Bool(false)
+    }
+}
+
+pub fn bytevector( args:~[Expression]) -> Expression {
+    return_first_error!()
+
+    let mut bytes:~[u8] = ~[];
+    for args.each() |arg| {
+        match byte( ~"bytevector", arg ) {
+            Ok(b) => bytes.push(b),
+            Err(error) => return error
         }
     }
-    return Bool(true);
+    Bytevector(@mut bytes)
 }
 
-macro_rules! assert_arg_count_range {
-    ($function:expr $minimum:expr $maximum:expr) => {
-        if args.len() > $maximum || args.len() < $minimum {
-            let head = fmt!("Built-in function '%s' takes", $function);
-            let middle = if $minimum == $maximum {
-                fmt!("only %u argument%s.", $minimum, sometimes_ess($minimum))
-            } else {
-                fmt!("betwen %u and %u arguments.", $minimum, $maximum)
-            };
-            let tail = fmt!("It was called with %u '%s'.", args.len(), List(args).to_str());
+pub fn make_bytevector( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"make-bytevector" 1 2 )
 
-            return Error( fmt!("%s %s %s", head, middle, tail) );
+    let fill = if args.len() == 2 {
+        match byte( ~"make-bytevector", &args[1] ) {
+            Ok(b) => b,
+            Err(error) => return error
         }
+    } else {
+        0u8
+    };
+    match args[0] {
+        Int(k) if k >= 0 => Bytevector(@mut vec::from_elem(k as uint, fill)),
+        _ => new_error( TypeCondition, ~"Built-in function 'make-bytevector' requires a non-negative length", ~[copy args[0]] )
     }
 }
 
-pub fn not( args:~[Expression]) -> Expression {
+pub fn bytevector_length( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"not" 1 1 )
+    assert_arg_count_range!( ~"bytevector-length" 1 1 )
 
-    return Bool(!args[0].to_bool());
+    match copy args[0] {
+        Bytevector(bytes) => Int(bytes.len() as int),
+        _ => new_error( TypeCondition, ~"Built-in function 'bytevector-length' requires a bytevector argument", ~[copy args[0]] )
+    }
 }
 
-pub fn car( args:~[Expression]) -> Expression {
+fn bytevector_index( name:&str, bytes:@mut ~[u8], index:&Expression ) -> Result<uint, Expression> {
+    match *index {
+        Int(k) if k >= 0 && (k as uint) < bytes.len() => Ok(k as uint),
+        Int(k) => Err(new_error( TypeCondition, fmt!("Built-in function '%s' was given an index outside of 0 to %u", name, bytes.len()), ~[Int(k)] )),
+        _ => Err(new_error( TypeCondition, fmt!("Built-in function '%s' requires an integer index", name), ~[copy *index] ))
+    }
+}
+
+pub fn bytevector_u8_ref( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"car" 1 1 )
+    assert_arg_count_range!( ~"bytevector-u8-ref" 2 2 )
 
     match copy args[0] {
-        List(list) => list.head(),
-        _ => Error( fmt!("Built-in function 'car' requires a list argument. It was called with %s", args[0].to_str()) )
+        Bytevector(bytes) => match bytevector_index( ~"bytevector-u8-ref", bytes, &args[1] ) {
+            Ok(k) => Int((*bytes)[k] as int),
+            Err(error) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'bytevector-u8-ref' requires a bytevector argument", ~[copy args[0]] )
     }
 }
 
-pub fn cdr( args:~[Expression]) -> Expression {
+pub fn bytevector_u8_set( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"cdr" 1 1 )
+    assert_arg_count_range!( ~"bytevector-u8-set!" 3 3 )
 
     match copy args[0] {
-        List(list) => List(list.tail()),
-        _ => Error( fmt!("Built-in function 'cdr' requires a list argument. It was called with %s", args[0].to_str()) )
+        Bytevector(bytes) => match (bytevector_index( ~"bytevector-u8-set!", bytes, &args[1] ), byte( ~"bytevector-u8-set!", &args[2] )) {
+            (Ok(k), Ok(b)) => {
+                (*bytes)[k] = b;
+                Unspecified
+            }
+            (Err(error), _) | (_, Err(error)) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'bytevector-u8-set!' requires a bytevector argument", ~[copy args[0]] )
     }
 }
 
-pub fn cons( args:~[Expression]) -> Expression {
+pub fn bytevector_copy( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"cons" 2 2 )
+    assert_arg_count_range!( ~"bytevector-copy" 1 3 )
 
-    match copy args[1] {
-        List(list) => List( ~[args[0]] + list ),
-        x => List( ~[args[0]] + ~[x] )
+    match copy args[0] {
+        Bytevector(bytes) => match optional_range( ~"bytevector-copy", args, 1, bytes.len() ) {
+            Ok((start, end)) => Bytevector(@mut vec::slice(*bytes, start, end)),
+            Err(error) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'bytevector-copy' requires a bytevector argument", ~[copy args[0]] )
     }
 }
 
-pub fn append( args:~[Expression]) -> Expression {
+pub fn bytevector_append( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"append" 2 2 )
+
+    let mut result:~[u8] = ~[];
+    for args.each() |&arg| {
+        match arg {
+            Bytevector(bytes) => result += copy *bytes,
+            _ => return new_error( TypeCondition, ~"Built-in function 'bytevector-append' requires bytevector arguments", copy args )
+        }
+    }
+    Bytevector(@mut result)
+}
+
+pub fn utf8_to_string( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"utf8->string" 1 3 )
 
     match copy args[0] {
-        List(list1) => {
-            match copy args[1] {
-                List(list) => List( list1 + list ),
-                x => List( list1 + ~[x] )
+        Bytevector(bytes) => match optional_range( ~"utf8->string", args, 1, bytes.len() ) {
+            Ok((start, end)) => {
+                let slice = vec::slice(*bytes, start, end);
+                if str::is_utf8(slice) {
+                    String(str::from_bytes(slice))
+                } else {
+                    new_error( TypeCondition, ~"Built-in function 'utf8->string' was given bytes that are not valid UTF-8", ~[Bytevector(@mut slice)] )
+                }
             }
-        }
-        _ => Error( fmt!("Built-in function 'append' requires a list as the first arguments. It was called with %s", List(args).to_str()) )
+            Err(error) => error
+        },
+        _ => new_error( TypeCondition, ~"Built-in function 'utf8->string' requires a bytevector argument", ~[copy args[0]] )
     }
 }
 
-pub fn length( args:~[Expression]) -> Expression {
+pub fn string_to_utf8( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"length" 1 1 )
-    
+    assert_arg_count_range!( ~"string->utf8" 1 3 )
+
     match copy args[0] {
-        List(list) => Int(list.len() as int),
-        _ => Error( fmt!("Built-in function 'length' requires a list argument. It was called with %s", List(args).to_str()) )
+        String(string) => {
+            let chars = str::chars(string);
+            match optional_range( ~"string->utf8", args, 1, chars.len() ) {
+                Ok((start, end)) => Bytevector(@mut str::to_bytes(str::from_chars(vec::slice(chars, start, end)))),
+                Err(error) => error
+            }
+        }
+        _ => new_error( TypeCondition, ~"Built-in function 'string->utf8' requires a string argument", ~[copy args[0]] )
     }
 }
 
-pub fn equal_( args:~[Expression] ) -> Expression {
+pub fn list( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"equal?" 2 2 )
-    
-    Bool(args[0] == args[1])
+    new_list(args)
 }
 
+// whether the procedure is the builtin function, found by where its code is, which
+// stays the same whatever name it is bound to
+fn is_builtin( procedure:&Expression, function:~fn(~[Expression]) -> Expression ) -> bool {
+    *procedure == new_proc( ~"", function )
+}
 
-pub fn symbol_( args:~[Expression]) -> Expression {
+pub fn make_hash_table( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"symbol?" 1 1 )
-    
+    assert_arg_count_range!( ~"make-hash-table" 0 1 )
+
+    let equivalence = if args.len() == 0 {
+        Equal
+    } else {
+        let procedure = &args[0];
+        if is_builtin( procedure, equal_ ) {
+            Equal
+        } else if is_builtin( procedure, eqv_ ) || is_builtin( procedure, eq_ ) {
+            Eqv
+        } else if is_builtin( procedure, string_equals ) {
+            StringEquals
+        } else {
+            return new_error( TypeCondition, ~"Built-in function 'make-hash-table' requires equal?, eqv?, eq? or string=?", ~[copy args[0]] )
+        }
+    };
+    HashTable(@mut Table::new(equivalence))
+}
+
+fn hash_table( name:&str, arg:&Expression ) -> Result<@mut Table, Expression> {
+    match copy *arg {
+        HashTable(table) => Ok(table),
+        _ => Err(new_error( TypeCondition, fmt!("Built-in function '%s' requires a hash table", name), ~[copy *arg] ))
+    }
+}
+
+// the table in args[0], when it can use args[1] as a key
+fn table_and_key( name:&str, args:&[Expression] ) -> Result<@mut Table, Expression> {
+    match hash_table( name, &args[0] ) {
+        Ok(table) => if table.accepts(&args[1]) {
+            Ok(table)
+        } else {
+            Err(new_error( TypeCondition, fmt!("Built-in function '%s' requires string keys for a string=? table", name), ~[copy args[1]] ))
+        },
+        Err(error) => Err(error)
+    }
+}
+
+// the value of args[1], passed through the procedure after failure if there is one,
+// or the result of calling the procedure at failure when the key is missing
+fn lookup( name:&str, table:@mut Table, args:&[Expression], failure:uint ) -> Expression {
+    match table.find(&args[1]) {
+        Some(value) => if args.len() > failure + 1 { apply( copy args[failure + 1], ~[value] ) } else { value },
+        None => if args.len() > failure {
+            apply( copy args[failure], ~[] )
+        } else {
+            new_error( TypeCondition, fmt!("Built-in function '%s' found no entry for the key", name), ~[copy args[1]] )
+        }
+    }
+}
+
+pub fn hash_table_( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"hash-table?" 1 1 )
+
     match args[0] {
-        Symbol(_) => Bool(true),
+        HashTable(_) => Bool(true),
         _ => Bool(false)
     }
 }
 
-pub fn list_( args:~[Expression]) -> Expression {
+pub fn hash_table_size( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"list?" 1 1 )
-    
-    match args[0] {
-        List(_) => Bool(true),
-        _ => Bool(false)
+    assert_arg_count_range!( ~"hash-table-size" 1 1 )
+
+    match hash_table( ~"hash-table-size", &args[0] ) {
+        Ok(table) => Int(table.size as int),
+        Err(error) => error
     }
 }
 
-pub fn null_( args:~[Expression]) -> Expression {
+pub fn hash_table_contains( args:~[Expression]) -> Expression {
     return_first_error!()
-    assert_arg_count_range!( ~"null?" 1 1 )
-    
+    assert_arg_count_range!( ~"hash-table-contains?" 2 2 )
+
+    match table_and_key( ~"hash-table-contains?", args ) {
+        Ok(table) => Bool(table.find(&args[1]).is_some()),
+        Err(error) => error
+    }
+}
+
+pub fn hash_table_ref( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"hash-table-ref" 2 4 )
+
+    match table_and_key( ~"hash-table-ref", args ) {
+        Ok(table) => lookup( ~"hash-table-ref", table, args, 2 ),
+        Err(error) => error
+    }
+}
+
+pub fn hash_table_ref_default( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"hash-table-ref/default" 3 3 )
+
+    match table_and_key( ~"hash-table-ref/default", args ) {
+        Ok(table) => table.find(&args[1]).get_default(copy args[2]),
+        Err(error) => error
+    }
+}
+
+pub fn hash_table_set( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"hash-table-set!" 3 3 )
+    // the value is stored whatever it is, even a condition
+    for vec::slice(args, 0, 2).each() |&arg| {
+        if arg.is_error() {
+            return arg;
+        }
+    }
+
+    match table_and_key( ~"hash-table-set!", args ) {
+        Ok(table) => {
+            table.insert(copy args[1], copy args[2]);
+            Unspecified
+        }
+        Err(error) => error
+    }
+}
+
+pub fn hash_table_delete( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"hash-table-delete!" 2 2 )
+
+    match table_and_key( ~"hash-table-delete!", args ) {
+        Ok(table) => {
+            table.remove(&args[1]);
+            Unspecified
+        }
+        Err(error) => error
+    }
+}
+
+// sets a key to the updater applied to its value, which is looked up like hash-table-ref
+pub fn hash_table_update( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"hash-table-update!" 3 5 )
+
+    let table = match table_and_key( ~"hash-table-update!", args ) {
+        Ok(table) => table,
+        Err(error) => return error
+    };
+    let current = lookup( ~"hash-table-update!", table, args, 3 );
+    if current.is_error() {
+        return current;
+    }
+    let updated = apply( copy args[2], ~[current] );
+    if updated.is_error() {
+        return updated;
+    }
+    table.insert(copy args[1], updated);
+    Unspecified
+}
+
+pub fn hash_table_keys( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"hash-table-keys" 1 1 )
+
+    match hash_table( ~"hash-table-keys", &args[0] ) {
+        Ok(table) => new_list(table.entries().map(|entry| entry.first())),
+        Err(error) => error
+    }
+}
+
+pub fn hash_table_to_alist( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"hash-table->alist" 1 1 )
+
+    match hash_table( ~"hash-table->alist", &args[0] ) {
+        Ok(table) => new_list(table.entries().map(|entry| new_pair(entry.first(), entry.second()))),
+        Err(error) => error
+    }
+}
+
+// calls args[1] with each key and value
+pub fn hash_table_walk( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_arg_count_range!( ~"hash-table-walk" 2 2 )
+
+    match hash_table( ~"hash-table-walk", &args[0] ) {
+        Ok(table) => {
+            for table.entries().each() |entry| {
+                let result = apply( copy args[1], ~[entry.first(), entry.second()] );
+                if result.is_error() {
+                    return result;
+                }
+            }
+            Unspecified
+        }
+        Err(error) => error
+    }
+}
+
+#[test]
+fn test_hash_tables() {
+    test_eval( ~"(begin (define h (make-hash-table)) (hash-table-set! h (list 1 \"a\") 1) (hash-table-ref h (list 1 \"a\")))", ~"1" );
+    test_eval( ~"(begin (define h (make-hash-table eqv?)) (hash-table-set! h (list 1) 1) (hash-table-ref/default h (list 1) (quote missing)))", ~"missing" );
+    test_eval( ~"(begin (define h (make-hash-table string=?)) (hash-table-set! h \"a\" 1) (hash-table-set! h \"a\" 2) (list (hash-table-ref h \"a\") (hash-table-size h)))", ~"(2 1)" );
+    test_eval( ~"(begin (define h (make-hash-table)) (hash-table-set! h 1 2) (hash-table-delete! h 1) (list (hash-table-contains? h 1) (hash-table-size h)))", ~"(#f 0)" );
+    test_eval( ~"(begin (define h (make-hash-table)) (hash-table-ref h (quote x) (lambda () (quote none))))", ~"none" );
+    test_eval( ~"(begin (define h (make-hash-table)) (hash-table-set! h (quote x) 4) (hash-table-ref h (quote x) (lambda () 0) (lambda (v) (* v 10))))", ~"40" );
+    test_eval( ~"(begin (define h (make-hash-table)) (define add1 (lambda (n) (+ n 1))) (hash-table-update! h (quote n) add1 (lambda () 0)) (hash-table-update! h (quote n) add1) (hash-table-ref h (quote n)))", ~"2" );
+    test_eval( ~"(begin (define h (make-hash-table)) (hash-table-set! h (quote a) 1) (list (hash-table-keys h) (hash-table->alist h)))", ~"((a) ((a . 1)))" );
+    test_eval( ~"(begin (define h (make-hash-table)) (define total 0) (hash-table-set! h 1 10) (hash-table-set! h 2 20) (hash-table-walk h (lambda (k v) (set! total (+ total k v)))) total)", ~"33" );
+    test_eval( ~"(list (hash-table? (make-hash-table)) (hash-table? (list)))", ~"(#t #f)" );
+    test_eval( ~"(begin (define h (make-hash-table)) (eq? h h))", ~"#t" );
+    test_eval_to_error( ~"(hash-table-ref (make-hash-table) 1)", ~"missing key" );
+    test_eval_to_error( ~"(hash-table-set! (make-hash-table string=?) 1 2)", ~"non-string key" );
+    test_eval( ~"(begin (define same? string=?) (define h (make-hash-table same?)) (hash-table-set! h \"a\" 1) (hash-table-ref h \"a\"))", ~"1" );
+    test_eval( ~"(begin (define h (make-hash-table)) (hash-table-set! h 1 (car 1)) (hash-table-contains? h 1))", ~"#t" );
+    test_eval( ~"(begin (define h (make-hash-table)) (define k (list 1)) (set-cdr! k k) (hash-table-set! h k 1) (hash-table-set! h k 2) (list (hash-table-ref h k) (hash-table-size h)))", ~"(2 1)" );
+    test_eval_to_error( ~"(make-hash-table 5)", ~"not an equivalence" );
+    test_eval_to_error( ~"(make-hash-table car)", ~"not an equivalence" );
+    test_eval_to_error( ~"(hash-table-update! (make-hash-table) 1 car)", ~"missing key without a failure procedure" );
+}
+
+// (error message irritant ...), a condition that the caller passes on like any other
+pub fn error( args:~[Expression]) -> Expression {
+    return_first_error!()
+    assert_mininum_number_of_args!( ~"error" 1 )
+
+    match copy args[0] {
+        String(message) => new_error( UserCondition, message, args.tail() ),
+        _ => new_error( TypeCondition, ~"Built-in function 'error' requires a string message", ~[copy args[0]] )
+    }
+}
+
+// the error object procedures take conditions as arguments, so unlike the other
+// builtins they do not return the first one they are given
+fn error_object( name:&str, arg:&Expression ) -> Result<@Condition, Expression> {
+    match copy *arg {
+        Error(condition) => Ok(condition),
+        _ => Err(new_error( TypeCondition, fmt!("Built-in function '%s' requires an error object", name), ~[copy *arg] ))
+    }
+}
+
+pub fn error_object_( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"error-object?" 1 1 )
+
+    Bool( args[0].is_error() )
+}
+
+pub fn error_object_message( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"error-object-message" 1 1 )
+
+    match error_object( ~"error-object-message", &args[0] ) {
+        Ok(condition) => String(copy condition.message),
+        Err(error) => error
+    }
+}
+
+pub fn error_object_irritants( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"error-object-irritants" 1 1 )
+
+    match error_object( ~"error-object-irritants", &args[0] ) {
+        Ok(condition) => new_list(copy condition.irritants),
+        Err(error) => error
+    }
+}
+
+pub fn file_error_( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"file-error?" 1 1 )
+
     match copy args[0] {
-        List(list) => Bool(list.len() == 0),
+        Error(condition) => Bool(condition.is_file_error()),
         _ => Bool(false)
     }
 }
 
-pub fn list( args:~[Expression]) -> Expression {
-    return_first_error!()
-    List(args)
+pub fn read_error_( args:~[Expression]) -> Expression {
+    assert_arg_count_range!( ~"read-error?" 1 1 )
+
+    match copy args[0] {
+        Error(condition) => Bool(condition.is_read_error()),
+        _ => Bool(false)
+    }
+}
+
+#[test]
+fn test_error_objects() {
+    test_eval( ~"(error-object? (error \"Something bad\" 1 2))", ~"#t" );
+    test_eval( ~"(error-object? (quote error))", ~"#f" );
+    test_eval( ~"(error-object-message (error \"Something bad\" 1 2))", ~"\"Something bad\"" );
+    test_eval( ~"(error-object-irritants (error \"Something bad\" 1 (quote (a b))))", ~"(1 (a b))" );
+    test_eval( ~"(error-object-irritants (error \"Something bad\"))", ~"()" );
+    test_eval( ~"(error-object-message (car 5))", ~"\"Built-in function 'car' requires a non-empty list or pair argument\"" );
+    test_eval( ~"(error-object-irritants (car 5))", ~"(5)" );
+    test_eval( ~"(begin (define e (vector-ref (vector 1) 3)) (list (error-object? e) (file-error? e) (read-error? e)))", ~"(#t #f #f)" );
+    test_eval( ~"(begin (define e (error \"boom\" 1 (quote x))) (list (error-object-message e) (error-object-irritants e)))", ~"(\"boom\" (1 x))" );
+    test_eval( ~"(begin (define e (error \"boom\")) (list (eq? e e) (eqv? e e) (equal? e e) (equal? e (error \"boom\"))))", ~"(#t #t #t #f)" );
+    test_eval( ~"(list (file-error? 1) (read-error? \"x\"))", ~"(#f #f)" );
+    test_eval_to_error( ~"(error \"Something bad\" 1)", ~"error makes an error" );
+    test_eval_to_error( ~"(error 5)", ~"message is not a string" );
+    test_eval_to_error( ~"(error-object-message 5)", ~"not an error object" );
 }
 
 pub fn eq_( args:~[Expression]) -> Expression {
-    return_first_error!()
     assert_arg_count_range!( ~"eq?" 2 2 )
 
-    Bool( args[0] == args[1] )
+    Bool( args[0].is_eqv(&args[1]) )
 }
 
 pub fn eqv_( args:~[Expression]) -> Expression {
-    return_first_error!()
     assert_arg_count_range!( ~"eqv?" 2 2 )
 
     eq_( args )
@@ -245,12 +1437,13 @@ fn test_eqv_() {
     assert( Bool(false) == eqv__( ~[ Int(2), Int(1) ] ) );
     assert( Bool(true) == eqv__( ~[ Float(1.0), Float(1.0) ] ) );
     assert( Bool(false) == eqv__( ~[ Float(2.0), Float(1.0) ] ) );
-    assert( Bool(true) == eqv__( ~[ List( ~[] ), List( ~[] ) ] ) );
-    assert( Bool(false) == eqv__( ~[ List( ~[Int(1)] ), List( ~[] ) ] ) );
+    assert( Bool(true) == eqv__( ~[ Nil, Nil ] ) );
+    assert( Bool(false) == eqv__( ~[ new_list( ~[Int(1)] ), Nil ] ) );
+    assert( Bool(false) == eqv__( ~[ new_list( ~[Int(1)] ), new_list( ~[Int(1)] ) ] ) );
 
     let env = @Environment::new_global_environment();
-    let proc = eval( parse("(lambda (x) (* x x))"), env ).first();
-    let proc2 = eval( parse("(lambda (x) (* x x))"), env ).first();
+    let proc = eval( test_parse("(lambda (x) (* x x))"), env ).first();
+    let proc2 = eval( test_parse("(lambda (x) (* x x))"), env ).first();
     assert( Bool(true) == eqv__( ~[ proc, proc ] ) );
     assert( Bool(false) == eqv__( ~[ proc, proc2 ] ) );
 }
@@ -265,6 +1458,85 @@ fn test_math() {
     test_eval( ~"(= 4 4)", ~"#t" );
 }
 
+#[test]
+fn test_rationals() {
+    test_eval( ~"(/ 1 3)", ~"1/3" );
+    test_eval( ~"(/ 7 2)", ~"7/2" );
+    test_eval( ~"(/ 6 4)", ~"3/2" );
+    test_eval( ~"(/ 6 3)", ~"2" );
+    test_eval( ~"(/ 2)", ~"1/2" );
+    test_eval( ~"(+ 1/3 1/6)", ~"1/2" );
+    test_eval( ~"(- 1/2 1/2)", ~"0" );
+    test_eval( ~"(* 2/3 3/2)", ~"1" );
+    test_eval( ~"(+ 1/2 0.5)", ~"1.0" );
+    test_eval( ~"(< 1/3 0.34 1/2)", ~"#t" );
+    test_eval( ~"(numerator 6/4)", ~"3" );
+    test_eval( ~"(denominator 6/4)", ~"2" );
+    test_eval( ~"(denominator 5)", ~"1" );
+    test_eval( ~"(denominator 0.5)", ~"2.0" );
+    test_eval( ~"(exact 0.25)", ~"1/4" );
+    test_eval( ~"(exact 2.0)", ~"2" );
+    test_eval( ~"(inexact 1/4)", ~"0.25" );
+    test_eval( ~"(rationalize (exact .3) 1/10)", ~"1/3" );
+    test_eval( ~"(rationalize .3 1/10)", ~"0.3333333333333333" );
+    test_eval( ~"(rationalize 5/2 -1)", ~"2" );
+    test_eval_to_error( ~"(/ 1 0)", ~"exact division by zero" );
+    test_eval_to_error( ~"(exact +inf.0)", ~"infinity has no exact value" );
+    test_eval_to_error( ~"(numerator (quote a))", ~"numerator of a symbol" );
+}
+
+#[test]
+fn test_numeric_tower() {
+    test_eval( ~"(= 1 1.0)", ~"#t" );
+    test_eval( ~"(= 1/2 0.5 2/4)", ~"#t" );
+    test_eval( ~"(= 1/3 0.3333333333333333)", ~"#f" );
+    test_eval( ~"(= 1+2i 1+2i)", ~"#t" );
+    test_eval( ~"(eqv? 1 1.0)", ~"#f" );
+    test_eval( ~"(exact? 1/2)", ~"#t" );
+    test_eval( ~"(exact? 0.5)", ~"#f" );
+    test_eval( ~"(inexact? 1+2i)", ~"#t" );
+    test_eval( ~"(exact->inexact 1/8)", ~"0.125" );
+    test_eval( ~"(inexact->exact 0.125)", ~"1/8" );
+    test_eval( ~"(list (number? 1+i) (complex? 1) (real? 1+i) (real? 1.5))", ~"(#t #t #f #t)" );
+    test_eval( ~"(list (rational? 1/2) (rational? +inf.0) (integer? 2.0) (integer? 5/2))", ~"(#t #f #t #f)" );
+    test_eval( ~"(* 3+4i 3-4i)", ~"25.0" );
+    test_eval( ~"(+ 1/2 +i)", ~"0.5+1.0i" );
+    test_eval( ~"(make-rectangular 3 4)", ~"3+4i" );
+    test_eval( ~"(make-rectangular 3 0)", ~"3" );
+    test_eval( ~"(make-polar 2 0)", ~"2" );
+    test_eval( ~"(real-part 3+4i)", ~"3.0" );
+    test_eval( ~"(imag-part 3+4i)", ~"4.0" );
+    test_eval( ~"(imag-part 7)", ~"0" );
+    test_eval( ~"(magnitude 3+4i)", ~"5.0" );
+    test_eval( ~"(magnitude -5/2)", ~"5/2" );
+    test_eval( ~"(angle +i)", ~"1.5707963267948966" );
+    test_eval( ~"(angle -1)", ~"3.141592653589793" );
+    test_eval( ~"(sqrt 16)", ~"4" );
+    test_eval( ~"(sqrt 9/4)", ~"3/2" );
+    test_eval( ~"(sqrt 2)", ~"1.4142135623730951" );
+    test_eval( ~"(sqrt -4)", ~"+2i" );
+    test_eval( ~"(sqrt -2.0)", ~"+1.4142135623730951i" );
+    test_eval( ~"(sqrt -3-4i)", ~"1-2i" );
+    test_eval( ~"(sqrt 100000000000000000000)", ~"10000000000" );
+    test_eval( ~"(sqrt 100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000)", ~"10000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000" );
+    test_eval( ~"(sqrt 81129638414606699710187514626049)", ~"9007199254740993" );
+    test_eval_to_error( ~"(< 1 +i)", ~"complex numbers are not ordered" );
+    test_eval_to_error( ~"(= 1 (quote a))", ~"comparing a symbol" );
+    test_eval_to_error( ~"(exact 1+2i)", ~"no exact complex numbers" );
+    test_eval_to_error( ~"(make-rectangular 1 +i)", ~"complex part" );
+}
+
+#[test]
+fn test_big_integers() {
+    test_eval( ~"(* 99999999999 99999999999)", ~"9999999999800000000001" );
+    test_eval( ~"(begin (define fact (lambda (n) (if (<= n 1) 1 (* n (fact (- n 1)))))) (fact 30))", ~"265252859812191058636308480000000" );
+    test_eval( ~"(- (+ 9223372036854775807 1) 1)", ~"9223372036854775807" );
+    test_eval( ~"(/ 265252859812191058636308480000000 (* 30 29))", ~"304888344611713860501504000000" );
+    test_eval( ~"(< 9223372036854775807 99999999999999999999)", ~"#t" );
+    test_eval( ~"(= 99999999999999999999 99999999999999999999)", ~"#t" );
+    test_eval( ~"(+ 99999999999999999999 0.5)", ~"1e20" );
+}
+
 #[test]
 fn test_list() {
     test_eval( ~"(list? (quote ()))", ~"#t" );
@@ -279,10 +1551,179 @@ fn test_list() {
     test_eval( ~"(length (list 1 2))", ~"2" );
 }
 
+#[test]
+fn test_dotted_pairs() {
+    test_eval( ~"(cons 1 2)", ~"(1 . 2)" );
+    test_eval( ~"(cons 1 (cons 2 3))", ~"(1 2 . 3)" );
+    test_eval( ~"(cons 1 (quote ()))", ~"(1)" );
+    test_eval( ~"(car (quote (1 . 2)))", ~"1" );
+    test_eval( ~"(cdr (quote (1 . 2)))", ~"2" );
+    test_eval( ~"(cdr (quote (1 2 . 3)))", ~"(2 . 3)" );
+    test_eval( ~"(append (list 1) 2)", ~"(1 . 2)" );
+    test_eval( ~"(append (list 1) (quote (2 . 3)))", ~"(1 2 . 3)" );
+    test_eval( ~"(list? (quote (1 . 2)))", ~"#f" );
+    test_eval( ~"(null? (quote (1 . 2)))", ~"#f" );
+    test_eval( ~"(equal? (cons 1 2) (quote (1 . 2)))", ~"#t" );
+    test_eval( ~"(car (car (quote ((a . 1) (b . 2)))))", ~"a" );
+    test_eval_to_error( ~"(length (quote (1 . 2)))", ~"length of an improper list" );
+    test_eval_to_error( ~"(car (quote ()))", ~"car of the empty list" );
+    test_eval_to_error( ~"(cdr (quote ()))", ~"cdr of the empty list" );
+}
+
+#[test]
+fn test_pairs() {
+    test_eval( ~"(pair? (cons 1 2))", ~"#t" );
+    test_eval( ~"(pair? (quote ()))", ~"#f" );
+    test_eval( ~"(pair? (vector 1))", ~"#f" );
+    test_eval( ~"(begin (define p (list 1 2)) (set-car! p 9) p)", ~"(9 2)" );
+    test_eval( ~"(begin (define p (list 1 2)) (set-cdr! p 3) p)", ~"(1 . 3)" );
+    test_eval( ~"(begin (define tail (list 2 3)) (define p (cons 1 tail)) (set-car! tail 9) p)", ~"(1 9 3)" );
+    test_eval( ~"(begin (define p (list 1 2)) (eq? (cdr p) (cdr p)))", ~"#t" );
+    test_eval( ~"(eq? (list 1) (list 1))", ~"#f" );
+    test_eval( ~"(begin (define p (list 1 2)) (set-cdr! (cdr p) p) (list? p))", ~"#f" );
+    test_eval( ~"(begin (define p (list 1 2)) (set-cdr! (cdr p) p) (equal? p p))", ~"#t" );
+    test_eval( ~"(begin (define p (list 1 2)) (set-cdr! (cdr p) p) (define q (list 1 2 1 2)) (set-cdr! (cdr (cdr (cdr q))) q) (equal? p q))", ~"#t" );
+    test_eval_to_error( ~"(set-car! (quote ()) 1)", ~"set-car! of the empty list" );
+    test_eval_to_error( ~"(set-cdr! 1 2)", ~"set-cdr! of a number" );
+}
+
+#[test]
+fn test_that_cyclic_lists_print_with_datum_labels() {
+    let result = eval(test_parse(~"(begin (define p (list 1 2)) (set-cdr! (cdr p) p) p)"), test_env()).first();
+    assert result.to_str() == ~"#0=(1 2 . #0#)";
+}
+
+#[test]
+fn test_strings() {
+    test_eval( ~"(string? \"a\")", ~"#t" );
+    test_eval( ~"(string? (quote a))", ~"#f" );
+    test_eval( ~"(string-length \"hello\")", ~"5" );
+    test_eval( ~"(string-length \"\")", ~"0" );
+    test_eval( ~"(string-append \"hello\" \" \" \"world\")", ~"\"hello world\"" );
+    test_eval( ~"(string-append)", ~"\"\"" );
+    test_eval( ~"(substring \"hello\" 1 3)", ~"\"el\"" );
+    test_eval( ~"(substring \"hello\" 2)", ~"\"llo\"" );
+    test_eval( ~"(string=? \"a\" \"a\" \"a\")", ~"#t" );
+    test_eval( ~"(string=? \"a\" \"b\")", ~"#f" );
+    test_eval( ~"(string->symbol \"abc\")", ~"abc" );
+    test_eval( ~"(string->symbol \"hello world\")", ~"|hello world|" );
+    test_eval( ~"(symbol->string '|hello world|)", ~"\"hello world\"" );
+    test_eval( ~"(symbol->string (quote abc))", ~"\"abc\"" );
+    test_eval( ~"(equal? \"abc\" \"abc\")", ~"#t" );
+    test_eval_to_error( ~"(substring \"hello\" 2 9)", ~"substring past the end of the string" );
+    test_eval_to_error( ~"(string-length 1)", ~"string-length of a number" );
+}
+
+#[test]
+fn test_chars() {
+    test_eval( ~"(char? #\\a)", ~"#t" );
+    test_eval( ~"(char? \"a\")", ~"#f" );
+    test_eval( ~"(char->integer #\\A)", ~"65" );
+    test_eval( ~"(char->integer #\\x3bb)", ~"955" );
+    test_eval( ~"(integer->char 97)", ~"#\\a" );
+    test_eval( ~"(char-upcase #\\a)", ~"#\\A" );
+    test_eval( ~"(char-upcase #\\1)", ~"#\\1" );
+    test_eval( ~"(char-downcase #\\A)", ~"#\\a" );
+    test_eval( ~"(char-upcase #\\x3bb)", ~"#\\x39b" );
+    test_eval( ~"(char-downcase #\\x39b)", ~"#\\x3bb" );
+    test_eval( ~"(char-upcase #\\x3c2)", ~"#\\x3a3" );
+    test_eval( ~"(list (char-upcase #\\xe4) (char-downcase #\\xc4))", ~"(#\\xc4 #\\xe4)" );
+    test_eval( ~"(list (char-upcase #\\x142) (char-downcase #\\x141))", ~"(#\\x141 #\\x142)" );
+    test_eval( ~"(list (char-upcase #\\x44f) (char-downcase #\\x40e))", ~"(#\\x42f #\\x45e)" );
+    test_eval( ~"(list (char-upcase #\\xf7) (char-downcase #\\xd7) (char-upcase #\\x4e2d))", ~"(#\\xf7 #\\xd7 #\\x4e2d)" );
+    test_eval( ~"(char-alphabetic? #\\a)", ~"#t" );
+    test_eval( ~"(char-alphabetic? #\\space)", ~"#f" );
+    test_eval( ~"(char-numeric? #\\7)", ~"#t" );
+    test_eval( ~"(char-whitespace? #\\newline)", ~"#t" );
+    test_eval( ~"(char<? #\\a #\\b #\\c)", ~"#t" );
+    test_eval( ~"(char<? #\\a #\\c #\\b)", ~"#f" );
+    test_eval( ~"(char=? #\\a #\\a)", ~"#t" );
+    test_eval( ~"(char>=? #\\b #\\a #\\a)", ~"#t" );
+    test_eval_to_error( ~"(integer->char -1)", ~"negative code point" );
+    test_eval_to_error( ~"(char<? #\\a 1)", ~"comparing a character to a number" );
+}
+
+#[test]
+fn test_vectors() {
+    test_eval( ~"(vector? (vector))", ~"#t" );
+    test_eval( ~"(vector? (list))", ~"#f" );
+    test_eval( ~"(vector 1 2 3)", ~"#(1 2 3)" );
+    test_eval( ~"(make-vector 2 (quote a))", ~"#(a a)" );
+    test_eval( ~"(vector-length (make-vector 3))", ~"3" );
+    test_eval( ~"(vector-ref #(1 2 3) 1)", ~"2" );
+    test_eval( ~"(begin (define v (vector 1 2 3)) (vector-set! v 0 (quote x)) v)", ~"#(x 2 3)" );
+    test_eval( ~"(vector->list #(1 2 3))", ~"(1 2 3)" );
+    test_eval( ~"(vector->list #(1 2 3) 1)", ~"(2 3)" );
+    test_eval( ~"(vector->list #(1 2 3) 1 2)", ~"(2)" );
+    test_eval( ~"(list->vector (list 1 2))", ~"#(1 2)" );
+    test_eval( ~"(begin (define v (vector 1 2 3 4)) (vector-fill! v 0 1 3) v)", ~"#(1 0 0 4)" );
+    test_eval( ~"(vector-map (lambda (x) (* x x)) #(1 2 3))", ~"#(1 4 9)" );
+    test_eval( ~"(vector-map + #(1 2 3) #(10 20))", ~"#(11 22)" );
+    test_eval( ~"(begin (define total 0) (vector-for-each (lambda (x) (set! total (+ total x))) #(1 2 3)) total)", ~"6" );
+    test_eval( ~"(vector-copy #(1 2 3) 1)", ~"#(2 3)" );
+    test_eval( ~"(begin (define a (vector 1 2)) (define b (vector-copy a)) (vector-set! b 0 9) a)", ~"#(1 2)" );
+    test_eval( ~"(begin (define a (vector 1 2)) (define b a) (vector-set! b 0 9) a)", ~"#(9 2)" );
+    test_eval( ~"(eq? (vector 1) (vector 1))", ~"#f" );
+    test_eval( ~"(equal? (vector 1) (vector 1))", ~"#t" );
+    test_eval( ~"(begin (define v (vector 1)) (eq? v v))", ~"#t" );
+    test_eval_to_error( ~"(vector-ref #(1 2 3) 3)", ~"index past the end" );
+    test_eval_to_error( ~"(vector-ref #(1 2 3) -1)", ~"negative index" );
+    test_eval_to_error( ~"(vector-set! (vector) 0 1)", ~"setting an empty vector" );
+    test_eval_to_error( ~"(vector-copy #(1 2 3) 2 1)", ~"end before start" );
+    test_eval_to_error( ~"(vector->list #(1 2 3) 0 4)", ~"end past the length" );
+    test_eval_to_error( ~"(vector-map car #(1))", ~"error from the mapped procedure" );
+}
+
+#[test]
+fn test_that_cycles_built_with_vector_set_print_with_datum_labels() {
+    let result = eval(test_parse(~"(begin (define v (vector 1 2)) (vector-set! v 1 (list v v)) v)"), test_env()).first();
+    assert result.to_str() == ~"#0=#(1 (#0# #0#))";
+}
+
+#[test]
+fn test_that_pretty_print_checks_its_width() {
+    test_eval_to_error( ~"(pretty-print 1 0)", ~"zero width" );
+    test_eval_to_error( ~"(pretty-print 1 (quote wide))", ~"width is not a number" );
+}
+
+#[test]
+fn test_bytevectors() {
+    test_eval( ~"(bytevector? #u8(1))", ~"#t" );
+    test_eval( ~"(bytevector? #(1))", ~"#f" );
+    test_eval( ~"(bytevector 0 255 16)", ~"#u8(0 255 16)" );
+    test_eval( ~"(make-bytevector 2 7)", ~"#u8(7 7)" );
+    test_eval( ~"(bytevector-length (make-bytevector 3))", ~"3" );
+    test_eval( ~"(bytevector-u8-ref #u8(5 6 7) 2)", ~"7" );
+    test_eval( ~"(begin (define b (bytevector 1 2)) (bytevector-u8-set! b 1 255) b)", ~"#u8(1 255)" );
+    test_eval( ~"(bytevector-copy #u8(1 2 3 4) 1 3)", ~"#u8(2 3)" );
+    test_eval( ~"(bytevector-append #u8(1) #u8() #u8(2 3))", ~"#u8(1 2 3)" );
+    test_eval( ~"(utf8->string #u8(65 66 67))", ~"\"ABC\"" );
+    test_eval( ~"(utf8->string #u8(206 187 120) 0 2)", ~"\"\u03bb\"" );
+    test_eval( ~"(string->utf8 \"\u03bbx\")", ~"#u8(206 187 120)" );
+    test_eval( ~"(string->utf8 \"abc\" 1)", ~"#u8(98 99)" );
+    test_eval( ~"(eq? (bytevector) (bytevector))", ~"#f" );
+    test_eval( ~"(equal? (bytevector 1) #u8(1))", ~"#t" );
+    test_eval_to_error( ~"(bytevector 256)", ~"byte out of range" );
+    test_eval_to_error( ~"(bytevector-u8-ref #u8(1) 1)", ~"index past the end" );
+    test_eval_to_error( ~"(bytevector-u8-set! (bytevector 1) 0 -1)", ~"negative byte" );
+    test_eval_to_error( ~"(utf8->string #u8(255))", ~"invalid UTF-8" );
+}
+
 pub fn builtins() -> ~[(~str,~fn(~[Expression]) -> Expression)] {
     ~[ (~"+", add), (~"-", sub), (~"*", mul), (~"/", div),
        (~"<", lt), (~"<=", le), (~">", gt), (~">=", ge),
        (~"=", equals), (~"not", not),
+       (~"numerator", numerator), (~"denominator", denominator),
+       (~"exact", exact), (~"inexact", inexact),
+       (~"rationalize", rationalize),
+       (~"exact?", exact_), (~"inexact?", inexact_),
+       (~"exact->inexact", inexact), (~"inexact->exact", exact),
+       (~"number?", number_), (~"complex?", number_),
+       (~"real?", real_), (~"rational?", rational_), (~"integer?", integer_),
+       (~"make-rectangular", make_rectangular), (~"make-polar", make_polar),
+       (~"real-part", real_part), (~"imag-part", imag_part),
+       (~"magnitude", magnitude), (~"angle", angle),
+       (~"sqrt", sqrt),
        (~"car", car), (~"cdr", cdr),
        (~"cons", cons), (~"append", append),
        (~"list", list), (~"length", length),
@@ -290,6 +1731,70 @@ pub fn builtins() -> ~[(~str,~fn(~[Expression]) -> Expression)] {
        (~"equal?", equal_),
        (~"symbol?", symbol_),
        (~"list?", list_),
-       (~"null?", null_)
+       (~"pair?", pair_),
+       (~"set-car!", set_car), (~"set-cdr!", set_cdr),
+       (~"null?", null_),
+       (~"string?", string_),
+       (~"string-length", string_length),
+       (~"string-append", string_append),
+       (~"substring", substring),
+       (~"string=?", string_equals),
+       (~"string->symbol", string_to_symbol),
+       (~"symbol->string", symbol_to_string),
+       (~"display", display),
+       (~"write", write), (~"write-simple", write_simple),
+       (~"pretty-print", pretty_print),
+       (~"newline", newline),
+       (~"char?", char_),
+       (~"char->integer", char_to_integer),
+       (~"integer->char", integer_to_char),
+       (~"char-upcase", char_upcase),
+       (~"char-downcase", char_downcase),
+       (~"char-alphabetic?", char_alphabetic_),
+       (~"char-numeric?", char_numeric_),
+       (~"char-whitespace?", char_whitespace_),
+       (~"char-upper-case?", char_upper_case_),
+       (~"char-lower-case?", char_lower_case_),
+       (~"char=?", char_eq), (~"char<?", char_lt), (~"char<=?", char_le),
+       (~"char>?", char_gt), (~"char>=?", char_ge),
+       (~"vector?", vector_),
+       (~"vector", vector),
+       (~"make-vector", make_vector),
+       (~"vector-length", vector_length),
+       (~"vector-ref", vector_ref),
+       (~"vector-set!", vector_set),
+       (~"vector->list", vector_to_list),
+       (~"list->vector", list_to_vector),
+       (~"vector-fill!", vector_fill),
+       (~"vector-copy", vector_copy),
+       (~"vector-map", vector_map),
+       (~"vector-for-each", vector_for_each),
+       (~"bytevector?", bytevector_),
+       (~"bytevector", bytevector),
+       (~"make-bytevector", make_bytevector),
+       (~"bytevector-length", bytevector_length),
+       (~"bytevector-u8-ref", bytevector_u8_ref),
+       (~"bytevector-u8-set!", bytevector_u8_set),
+       (~"bytevector-copy", bytevector_copy),
+       (~"bytevector-append", bytevector_append),
+       (~"utf8->string", utf8_to_string),
+       (~"string->utf8", string_to_utf8),
+       (~"make-hash-table", make_hash_table),
+       (~"hash-table?", hash_table_),
+       (~"hash-table-size", hash_table_size),
+       (~"hash-table-contains?", hash_table_contains),
+       (~"hash-table-ref", hash_table_ref),
+       (~"hash-table-ref/default", hash_table_ref_default),
+       (~"hash-table-set!", hash_table_set),
+       (~"hash-table-delete!", hash_table_delete),
+       (~"hash-table-update!", hash_table_update),
+       (~"hash-table-keys", hash_table_keys),
+       (~"hash-table->alist", hash_table_to_alist),
+       (~"hash-table-walk", hash_table_walk),
+       (~"error", error),
+       (~"error-object?", error_object_),
+       (~"error-object-message", error_object_message),
+       (~"error-object-irritants", error_object_irritants),
+       (~"file-error?", file_error_), (~"read-error?", read_error_)
     ]
 }
@@ -0,0 +1,76 @@
+/*
+ * Conditions, the values that errors evaluate to
+ *
+ * A condition says what kind of thing went wrong, with a message and the
+ * values involved, which R7RS calls its irritants. Conditions the reader
+ * raises also know the line and column they were found at. Builtins hand
+ * back the first condition among their arguments, so a condition makes its
+ * way out to the top of the program unless error-object? and its accessors
+ * look at it on the way.
+ */
+
+pub enum ConditionKind {
+    // a special form written the wrong way
+    SyntaxCondition,
+    // a value of the wrong type, or out of the range the procedure accepts
+    TypeCondition,
+    // a procedure called with the wrong number of arguments
+    ArityCondition,
+    UnboundCondition,
+    FileCondition,
+    ReadCondition,
+    // raised by the error procedure
+    UserCondition
+}
+
+pub struct Condition {
+    kind: ConditionKind,
+    message: ~str,
+    irritants: ~[Expression],
+    // the line and column in the source, when it is known
+    location: Option<(uint, uint)>
+}
+
+pub impl Condition {
+    static pure fn new( kind:ConditionKind, message:~str, irritants:~[Expression] ) -> Condition {
+        Condition { kind: kind, message: message, irritants: irritants, location: None }
+    }
+
+    // a program that could not be read, at the place the reader stopped
+    static pure fn from_parse_error( error:&ParseError ) -> Condition {
+        Condition { kind: ReadCondition, message: error.message(), irritants: ~[], location: Some((error.line, error.column)) }
+    }
+
+    pure fn is_file_error(&self) -> bool {
+        match self.kind {
+            FileCondition => true,
+            _ => false
+        }
+    }
+
+    pure fn is_read_error(&self) -> bool {
+        match self.kind {
+            ReadCondition => true,
+            _ => false
+        }
+    }
+}
+
+#[test]
+fn test_that_conditions_start_without_a_location() {
+    let condition = Condition::new(TypeCondition, ~"Expected a number", ~[Symbol(~"a")]);
+    assert condition.location.is_none();
+    assert condition.irritants == ~[Symbol(~"a")];
+    assert !condition.is_file_error() && !condition.is_read_error();
+}
+
+#[test]
+fn test_that_parse_errors_become_read_conditions_where_they_happened() {
+    let condition = match parse(~"(a\n  \"b") {
+        Err(error) => Condition::from_parse_error(&error),
+        Ok(_) => fail ~"an unterminated string parsed"
+    };
+    assert condition.is_read_error();
+    assert condition.location == Some((2, 3));
+    assert condition.message == ~"Unterminated string literal";
+}
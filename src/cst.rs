@@ -0,0 +1,292 @@
+/*
+ * A concrete syntax tree that keeps every byte of the source
+ *
+ * parse::read throws away whitespace, comments and how each atom was spelled,
+ * which is right for evaluation but useless to a tool that rewrites source.
+ * The nodes here keep all of that, and each one knows its span. Atoms are not
+ * checked until the tree is lowered to expressions, so a tree can be built for
+ * any source whose brackets balance.
+ */
+
+use parse::{Token,ParseError,lex,read_all,closer,error_at_token};
+use parse::{UnexpectedCloseParen,MismatchedCloseParen,UnterminatedList,UnexpectedEndOfInput,MissingDatum};
+
+pub enum Node {
+    // whitespace, ; comments and #| |# comments
+    Trivia(Token),
+    // #!fold-case and #!no-fold-case
+    Directive(Token),
+    // a single token datum exactly as it was written, or the . of a dotted list
+    Atom(Token),
+    // ( [ #( or #u8(, everything inside, and the token that closes it
+    Compound(Token, ~[Node], Token),
+    // ' ` , ,@ #; or #n=, the trivia after it and the datum it applies to
+    Prefixed(Token, ~[Node], @Node)
+}
+
+pub impl Node {
+    pure fn first_token(&self) -> Token {
+        match copy *self {
+            Trivia(token) | Directive(token) | Atom(token) => token,
+            Compound(open, _, _) => open,
+            Prefixed(prefix, _, _) => prefix
+        }
+    }
+
+    pure fn last_token(&self) -> Token {
+        match copy *self {
+            Trivia(token) | Directive(token) | Atom(token) => token,
+            Compound(_, _, close) => close,
+            Prefixed(_, _, datum) => datum.last_token()
+        }
+    }
+
+    // byte offsets into the source
+    pure fn start(&self) -> uint {
+        self.first_token().start
+    }
+
+    pure fn end(&self) -> uint {
+        self.last_token().end
+    }
+
+    // everything the node was read from, byte for byte
+    pure fn source(&self) -> ~str {
+        str::concat(self.tokens().map(|token| copy token.text))
+    }
+
+    // every token under the node in source order, trivia included
+    pure fn tokens(&self) -> ~[Token] {
+        match copy *self {
+            Trivia(token) | Directive(token) | Atom(token) => ~[token],
+            Compound(open, contents, close) => ~[open] + all_tokens(contents) + ~[close],
+            Prefixed(prefix, gap, datum) => ~[prefix] + all_tokens(gap) + datum.tokens()
+        }
+    }
+
+    // true for nodes that read as nothing: whitespace, comments, directives and #; comments
+    pure fn is_trivia(&self) -> bool {
+        match copy *self {
+            Trivia(_) | Directive(_) => true,
+            Prefixed(prefix, _, _) => prefix.text == ~"#;",
+            _ => false
+        }
+    }
+}
+
+pub pure fn all_tokens( nodes:&[Node] ) -> ~[Token] {
+    let mut tokens:~[Token] = ~[];
+    for nodes.each() |node| {
+        tokens += node.tokens();
+    }
+    tokens
+}
+
+pub pure fn to_source( nodes:&[Node] ) -> ~str {
+    str::concat(nodes.map(|node| node.source()))
+}
+
+// the expressions the nodes stand for, read by the same reader parse::parse_all uses
+pub fn lower( nodes:&[Node] ) -> Result<~[Expression], ParseError> {
+    read_all(all_tokens(nodes).filtered(|token| !token.is_trivia()))
+}
+
+fn is_directive( token:&Token ) -> bool {
+    token.text == ~"#!fold-case" || token.text == ~"#!no-fold-case"
+}
+
+fn is_prefix( token:&Token ) -> bool {
+    match copy token.text {
+        ~"'" | ~"`" | ~"," | ~",@" | ~"#;" => true,
+        text => str::starts_with(text, "#") && str::ends_with(text, "=") &&
+            str::char_len(text) > 2 && str::all(str::slice(text, 1, text.len() - 1), char::is_digit)
+    }
+}
+
+struct Builder {
+    tokens: ~[Token],
+    mut position: uint
+}
+
+impl Builder {
+    fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn next(&self) -> Token {
+        let token = copy self.tokens[self.position];
+        self.position += 1;
+        token
+    }
+
+    fn next_is_close(&self) -> bool {
+        !self.at_end() && (self.tokens[self.position].text == ~")" || self.tokens[self.position].text == ~"]")
+    }
+
+    // the whitespace, comments and directives up to the next datum
+    fn gap(&self) -> ~[Node] {
+        let mut nodes:~[Node] = ~[];
+        while !self.at_end() {
+            let token = copy self.tokens[self.position];
+            if token.is_trivia() {
+                nodes.push(Trivia(token));
+            } else if is_directive(&token) {
+                nodes.push(Directive(token));
+            } else {
+                break;
+            }
+            self.position += 1;
+        }
+        nodes
+    }
+
+    // assumes the gap before it has been taken and there is at least one token left
+    fn node(&self) -> Result<Node, ParseError> {
+        let token = self.next();
+        match copy token.text {
+            ~"(" | ~"[" | ~"#(" | ~"#u8(" | ~"#U8(" => self.compound(token),
+            ~")" | ~"]" => Err(error_at_token(UnexpectedCloseParen, &token)),
+            _ => {
+                if is_prefix(&token) {
+                    self.prefixed(token)
+                } else {
+                    Ok(Atom(token))
+                }
+            }
+        }
+    }
+
+    fn compound(&self, open:Token) -> Result<Node, ParseError> {
+        let mut contents:~[Node] = ~[];
+        loop {
+            contents += self.gap();
+            if self.at_end() {
+                return Err(error_at_token(UnterminatedList, &open));
+            }
+            if self.next_is_close() {
+                let close = self.next();
+                if close.text != closer(&open) {
+                    return Err(error_at_token(MismatchedCloseParen(copy open.text, copy close.text), &close));
+                }
+                return Ok(Compound(open, contents, close));
+            }
+            match self.node() {
+                Ok(node) => contents.push(node),
+                Err(error) => return Err(error)
+            }
+        }
+    }
+
+    fn prefixed(&self, prefix:Token) -> Result<Node, ParseError> {
+        let gap = self.gap();
+        if self.at_end() {
+            Err(error_at_token(UnexpectedEndOfInput, &prefix))
+        } else if self.next_is_close() {
+            Err(error_at_token(MissingDatum(copy prefix.text), &prefix))
+        } else {
+            match self.node() {
+                Ok(datum) => Ok(Prefixed(prefix, gap, @datum)),
+                Err(error) => Err(error)
+            }
+        }
+    }
+}
+
+// the tree for a whole program: its top-level data with the trivia between them
+pub fn parse_cst( program:&str ) -> Result<~[Node], ParseError> {
+    let tokens = match lex( program ) {
+        Ok(tokens) => tokens,
+        Err(error) => return Err(error)
+    };
+    let builder = Builder { tokens: tokens, position: 0 };
+    let mut nodes:~[Node] = ~[];
+    loop {
+        nodes += builder.gap();
+        if builder.at_end() {
+            return Ok(nodes);
+        }
+        match builder.node() {
+            Ok(node) => nodes.push(node),
+            Err(error) => return Err(error)
+        }
+    }
+}
+
+fn cst_ok( program:&str ) -> ~[Node] {
+    match parse_cst( program ) {
+        Ok(nodes) => nodes,
+        Err(error) => fail fmt!("Could not build a tree for %s: %s", program, error.to_str())
+    }
+}
+
+#[test]
+fn test_that_the_tree_keeps_every_byte() {
+    let program = ~"; leading comment\n(define  (f x)   #| why |#\n  [* x #xFF]) #;(ignored)\n'#(1 . 2) #u8( 1 ) #0=(a) |odd sym| 1.50e1 #!fold-case\n";
+    assert to_source( cst_ok( program ) ) == program;
+}
+
+#[test]
+fn test_that_the_tree_keeps_spellings() {
+    match cst_ok( ~"#xFF" ) {
+        [Atom(token)] => assert token.text == ~"#xFF",
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_nodes_know_their_spans() {
+    let program = ~"  (a  b) 'c";
+    match cst_ok( program ) {
+        [Trivia(_), list, Trivia(_), quoted] => {
+            assert (list.start(), list.end()) == (2, 8);
+            assert str::slice(program, list.start(), list.end()) == list.source();
+            assert (quoted.start(), quoted.end()) == (9, 11);
+        }
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_comments_stay_inside_the_list_they_were_written_in() {
+    match cst_ok( ~"(a ; note\n b)" ) {
+        [Compound(_, contents, _)] => {
+            assert contents.len() == 5;
+            match copy contents[2] {
+                Trivia(token) => assert token.text == ~"; note",
+                _ => fail
+            }
+        }
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_datum_comments_and_directives_are_trivia() {
+    let nodes = cst_ok( ~"#;(a b) #!fold-case x" );
+    assert nodes.map(|node| node.is_trivia()) == ~[true, true, true, true, false];
+}
+
+#[test]
+fn test_that_lowering_gives_what_the_reader_gives() {
+    let program = ~"(define (f x) ; double it\n  (* 2 x)) #;(skipped) [a . b] #0=#(1 #0#) #!fold-case ABC";
+    let lowered = lower( cst_ok( program ) ).get();
+    let read = parse_all( program ).get();
+    assert lowered.len() == read.len();
+    assert lowered.map(|expression| expression.to_str()) == read.map(|expression| expression.to_str());
+}
+
+#[test]
+fn test_that_lowering_reports_bad_atoms() {
+    let nodes = cst_ok( ~"(a #q)" );
+    match lower( nodes ) {
+        Err(ParseError { kind: parse::BadHashSyntax(_), start: 3, end: 5, _ }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_unbalanced_source_has_no_tree() {
+    for [~"(a", ~"a)", ~"(a]", ~"'", ~"(a ')"].each |&program| {
+        assert parse_cst( program ).is_err();
+    }
+}
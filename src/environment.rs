@@ -40,7 +40,7 @@ pub impl Environment {
         let mapping:LinearMap<~str,Expression> = LinearMap();
         let env = Environment {mappings:@[@mut mapping]};
         for builtins::builtins().each() |&(name, function)| {
-            env.define(name, new_proc(function));
+            env.define(copy name, new_proc(name, function));
         }
         env
         //Environment {mappings:@[@mut mapping]}
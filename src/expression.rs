@@ -1,29 +1,136 @@
+use send_map::linear::LinearMap;
+
+// a mutable pair, which lists are chains of
+pub struct Cons {
+    car: Expression,
+    cdr: Expression
+}
+
 pub enum Expression {
     Bool(bool),
     Int(int),
+    // only for integers too large for an int; anything that fits is always an Int
+    Big(BigInt),
+    // only for exact numbers that are not integers
+    Rational(Fraction),
     Float(float),
+    // the real and imaginary parts of a number off the real line, which are always
+    // inexact; the imaginary part is never zero
+    Complex(float, float),
     Symbol(~str),
-    List(~[Expression]),
-    Proc(~fn(~[Expression]) -> Expression, (uint,uint)),
-    Lambda(@Expression,~[Expression],@Environment),
-    Error(~str)
+    String(~str),
+    Char(char),
+    // the empty list, which ends every proper list
+    Nil,
+    // what define, set! and the mutators return, which the REPL does not print
+    Unspecified,
+    Pair(@mut Cons),
+    Vector(@mut ~[Expression]),
+    Bytevector(@mut ~[u8]),
+    HashTable(@mut Table),
+    Record(@RecordType, @mut ~[Expression]),
+    Proc(~fn(~[Expression]) -> Expression, (uint,uint), ~str),
+    // the name is filled in when the lambda is bound by define
+    Lambda(@Expression,~[Expression],@Environment,Option<~str>),
+    // a constructor, predicate, accessor or modifier of a record type, and its name
+    RecordProcedure(@RecordType, RecordOperation, ~str),
+    Error(@Condition)
 } 
 
+// int arithmetic, or None when the result would not fit in an int
+pure fn checked_add( x:int, y:int ) -> Option<int> {
+    let result = x + y;
+    if (x >= 0) == (y >= 0) && (result >= 0) != (x >= 0) { None } else { Some(result) }
+}
+
+pure fn checked_sub( x:int, y:int ) -> Option<int> {
+    let result = x - y;
+    if (x >= 0) != (y >= 0) && (result >= 0) != (x >= 0) { None } else { Some(result) }
+}
+
+pure fn checked_mul( x:int, y:int ) -> Option<int> {
+    if x == 0 || y == 0 {
+        return Some(0);
+    }
+    let result = x * y;
+    if (x == -1 && y == int::min_value) || (y == -1 && x == int::min_value) || result / y != x {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+// int division when it comes out whole, or None when it needs a fraction or more room
+pure fn checked_div( x:int, y:int ) -> Option<int> {
+    if y == 0 || (x == int::min_value && y == -1) || x % y != 0 { None } else { Some(x / y) }
+}
+
+// arithmetic on exact numbers that are not both small enough for the int versions
+pure fn exact_add( x:Fraction, y:Fraction ) -> Expression {
+    Expression::from_fraction(x + y)
+}
+
+pure fn exact_sub( x:Fraction, y:Fraction ) -> Expression {
+    Expression::from_fraction(x - y)
+}
+
+pure fn exact_mul( x:Fraction, y:Fraction ) -> Expression {
+    Expression::from_fraction(x * y)
+}
+
+pure fn exact_div( x:Fraction, y:Fraction ) -> Expression {
+    if y.is_zero() {
+        Expression::new_error(TypeCondition, ~"Division by zero", ~[Expression::from_fraction(x)])
+    } else {
+        Expression::from_fraction(x / y)
+    }
+}
+
+// arithmetic on the real and imaginary parts when either number is complex
+pure fn complex_add( x:(float, float), y:(float, float) ) -> Expression {
+    let ((a, b), (c, d)) = (x, y);
+    Expression::new_complex(a + c, b + d)
+}
+
+pure fn complex_sub( x:(float, float), y:(float, float) ) -> Expression {
+    let ((a, b), (c, d)) = (x, y);
+    Expression::new_complex(a - c, b - d)
+}
+
+pure fn complex_mul( x:(float, float), y:(float, float) ) -> Expression {
+    let ((a, b), (c, d)) = (x, y);
+    Expression::new_complex(a * c - b * d, a * d + b * c)
+}
+
+pure fn complex_div( x:(float, float), y:(float, float) ) -> Expression {
+    let ((a, b), (c, d)) = (x, y);
+    let divisor = c * c + d * d;
+    Expression::new_complex((a * c + b * d) / divisor, (b * c - a * d) / divisor)
+}
+
 macro_rules! operator_overload {
-    ($operator_name:ident $function_name:ident) => (
+    ($operator_name:ident $function_name:ident $checked:ident $exact:ident $complex:ident) => (
         pub impl Expression: ops::$operator_name<Expression,Expression> {
             pure fn $function_name(&self, other:&Expression) -> Expression {
                 match (copy *self, copy *other) {
-                    (Int(x), Int(y)) => Int(x.$function_name(&y)),
-                    (Error(x), _) => Error(copy x),
-                    (_, Error(x)) => Error(copy x),
+                    (Int(x), Int(y)) => match $checked(x, y) {
+                        Some(result) => return Int(result),
+                        None => ()
+                    },
+                    (Error(x), _) | (_, Error(x)) => return Error(x),
+                    _ => ()
+                }
+                match (self.to_fraction(), other.to_fraction()) {
+                    (Some(x), Some(y)) => $exact(x, y),
                     _ => {
                         if !self.is_number() {
-                            Error(fmt!("%s was given where a number was expected", self.to_str()))
+                            Expression::new_error(TypeCondition, ~"Expected a number", ~[copy *self])
                         } else if !other.is_number() {
-                            Error(fmt!("%s was given where a number was expected", other.to_str()))
-                        } else {
+                            Expression::new_error(TypeCondition, ~"Expected a number", ~[copy *other])
+                        } else if self.is_real() && other.is_real() {
                             Float(self.to_float().$function_name(&other.to_float()))
+                        } else {
+                            $complex(self.to_complex(), other.to_complex())
                         }
                     }
                 }
@@ -32,44 +139,102 @@ macro_rules! operator_overload {
     )
 }
 
-operator_overload!(Add add)
-operator_overload!(Sub sub)
-operator_overload!(Mul mul)
-operator_overload!(Div div)
+operator_overload!(Add add checked_add exact_add complex_add)
+operator_overload!(Sub sub checked_sub exact_sub complex_sub)
+operator_overload!(Mul mul checked_mul exact_mul complex_mul)
+operator_overload!(Div div checked_div exact_div complex_div)
 
+// comparisons of real numbers, which are false whenever NaN or a complex number is involved
 pub impl Expression: cmp::Ord {
     pure fn lt(&self, other: &Expression) -> bool {
-        match (*self, *other) {
-            (Int(x), Int(y)) => x.lt(&y),
-            _ => self.to_float().lt(&other.to_float())
-        }
+        match self.compare_real(other) { Some(order) => order < 0, None => false }
     }
     pure fn le(&self, other: &Expression) -> bool {
-        match (*self, *other) {
-            (Int(x), Int(y)) => x.le(&y),
-            _ => self.to_float().le(&other.to_float())
-        }
+        match self.compare_real(other) { Some(order) => order <= 0, None => false }
     }
     pure fn gt(&self, other: &Expression) -> bool {
-        match (*self, *other) {
-            (Int(x), Int(y)) => x.gt(&y),
-            _ => self.to_float().gt(&other.to_float())
-        }
+        match self.compare_real(other) { Some(order) => order > 0, None => false }
     }
     pure fn ge(&self, other: &Expression) -> bool {
-        match (*self, *other) {
-            (Int(x), Int(y)) => x.ge(&y),
-            _ => self.to_float().ge(&other.to_float())
-        }
+        match self.compare_real(other) { Some(order) => order >= 0, None => false }
     }
 }
 
 pub impl Expression {
-    static fn new_proc( function:~fn(~[Expression]) -> Expression) -> Expression {
+    static pure fn new_pair( car:Expression, cdr:Expression ) -> Expression {
+        Pair(@mut Cons { car: car, cdr: cdr })
+    }
+
+    static pure fn new_list( items:~[Expression] ) -> Expression {
+        Expression::new_dotted(items, Nil)
+    }
+
+    // (a b . c), which is just tail when there are no heads
+    static pure fn new_dotted( heads:~[Expression], tail:Expression ) -> Expression {
+        let mut result = tail;
+        let mut i = heads.len();
+        while i > 0 {
+            i -= 1;
+            result = Expression::new_pair(copy heads[i], result);
+        }
+        result
+    }
+
+    // an Int when the value fits in one
+    static pure fn from_bigint( n:BigInt ) -> Expression {
+        match n.to_int() {
+            Some(small) => Int(small),
+            None => Big(n)
+        }
+    }
+
+    // an Int or Big when the fraction is a whole number
+    static pure fn from_fraction( f:Fraction ) -> Expression {
+        if f.is_integer() {
+            Expression::from_bigint(copy f.numerator)
+        } else {
+            Rational(f)
+        }
+    }
+
+    // a Float when the imaginary part is zero
+    static pure fn new_complex( real:float, imaginary:float ) -> Expression {
+        if imaginary == 0.0 {
+            Float(real)
+        } else {
+            Complex(real, imaginary)
+        }
+    }
+
+    // the number real + imaginary i, which stays real and keeps its exactness when the
+    // imaginary part is an exact zero
+    static pure fn new_rectangular( real:&Expression, imaginary:&Expression ) -> Expression {
+        match *imaginary {
+            Int(0) => copy *real,
+            _ => Expression::new_complex(real.to_float(), imaginary.to_float())
+        }
+    }
+
+    // the number with the given magnitude at angle radians from the positive real line
+    static pure fn new_polar( magnitude:&Expression, angle:&Expression ) -> Expression {
+        match *angle {
+            Int(0) => copy *magnitude,
+            _ => {
+                let (r, theta) = (magnitude.to_float(), angle.to_float());
+                Expression::new_complex(r * float::cos(theta), r * float::sin(theta))
+            }
+        }
+    }
+
+    static fn new_proc( name:~str, function:~fn(~[Expression]) -> Expression) -> Expression {
         let ptr:(uint,uint) = unsafe {
             cast::reinterpret_cast(&function)
         };
-        Proc( function, ptr )
+        Proc( function, ptr, name )
+    }
+
+    static pure fn new_error( kind:ConditionKind, message:~str, irritants:~[Expression] ) -> Expression {
+        Error(@Condition::new(kind, message, irritants))
     }
 
     pure fn is_error(&self) -> bool {
@@ -79,16 +244,151 @@ pub impl Expression {
         }
     }
 
+    // the cars along the chain of cdrs, and what ends the chain: the empty list for a
+    // proper list, anything else for an improper one, or a pair already passed through
+    // for a list that loops back on itself
+    pure fn list_parts(&self) -> (~[Expression], Expression) {
+        let mut items:~[Expression] = ~[];
+        let mut current = copy *self;
+        // moves at half the speed, so the walk can only catch up with it in a cycle
+        let mut behind = copy *self;
+        loop {
+            match copy current {
+                Pair(cell) => {
+                    items.push(copy cell.car);
+                    current = copy cell.cdr;
+                }
+                other => return (items, other)
+            }
+            if items.len() % 2 == 0 {
+                behind = match copy behind { Pair(cell) => copy cell.cdr, other => other };
+                match (copy current, copy behind) {
+                    (Pair(x), Pair(y)) if managed::mut_ptr_eq(x, y) => return (items, current),
+                    _ => ()
+                }
+            }
+        }
+    }
+
+    // the items of a proper list, or None for anything else
+    pure fn list_items(&self) -> Option<~[Expression]> {
+        match self.list_parts() {
+            (items, Nil) => Some(items),
+            _ => None
+        }
+    }
+
+    pure fn is_list(&self) -> bool {
+        self.list_items().is_some()
+    }
+
     pure fn is_number(&self) -> bool {
         match *self {
-            Int(_) | Float(_) => true,
+            Int(_) | Big(_) | Rational(_) | Float(_) | Complex(_, _) => true,
+            _ => false
+        }
+    }
+
+    pure fn is_real(&self) -> bool {
+        match *self {
+            Int(_) | Big(_) | Rational(_) | Float(_) => true,
+            _ => false
+        }
+    }
+
+    pure fn is_exact(&self) -> bool {
+        match *self {
+            Int(_) | Big(_) | Rational(_) => true,
             _ => false
         }
     }
 
+    // the value of an exact number
+    pure fn to_fraction(&self) -> Option<Fraction> {
+        match copy *self {
+            Int( number ) => Some(Fraction::from_integer(BigInt::from_int(number))),
+            Big( number ) => Some(Fraction::from_integer(number)),
+            Rational( number ) => Some(number),
+            _ => None
+        }
+    }
+
+    // the value of an exact number, or of a float other than an infinity or NaN
+    pure fn to_exact_fraction(&self) -> Option<Fraction> {
+        match *self {
+            Float(number) => {
+                if float::is_NaN(number) || float::is_infinite(number) {
+                    None
+                } else {
+                    Some(Fraction::from_float(number))
+                }
+            }
+            _ => self.to_fraction()
+        }
+    }
+
+    // the real and imaginary parts of a number
+    pure fn to_complex(&self) -> (float, float) {
+        match *self {
+            Complex(real, imaginary) => (real, imaginary),
+            _ => (self.to_float(), 0.0)
+        }
+    }
+
+    // the order of two real numbers as a negative, zero or positive int, or None when
+    // either is NaN or not real; an exact number and a float are compared exactly, so
+    // that 9007199254740993 is not equal to the float it rounds to
+    pure fn compare_real(&self, other:&Expression) -> Option<int> {
+        pure fn compare_floats( x:float, y:float ) -> Option<int> {
+            if float::is_NaN(x) || float::is_NaN(y) {
+                None
+            } else {
+                Some(if x < y { -1 } else if x > y { 1 } else { 0 })
+            }
+        }
+
+        if !self.is_real() || !other.is_real() {
+            return None;
+        }
+        match (copy *self, copy *other) {
+            (Int(x), Int(y)) => return Some(if x < y { -1 } else if x > y { 1 } else { 0 }),
+            (Float(x), Float(y)) => return compare_floats(x, y),
+            _ => ()
+        }
+        match (self.to_exact_fraction(), other.to_exact_fraction()) {
+            (Some(x), Some(y)) => Some(x.compare(&y)),
+            // an infinity or NaN, which the float comparison gets right
+            _ => compare_floats(self.to_float(), other.to_float())
+        }
+    }
+
+    // eqv?, which is structural equality except that pairs, vectors and bytevectors
+    // are only the same as themselves, as hash tables and records always are
+    pure fn is_eqv(&self, other:&Expression) -> bool {
+        match (copy *self, copy *other) {
+            (Pair(x), Pair(y)) => managed::mut_ptr_eq(x, y),
+            (Vector(x), Vector(y)) => managed::mut_ptr_eq(x, y),
+            (Bytevector(x), Bytevector(y)) => managed::mut_ptr_eq(x, y),
+            _ => *self == *other
+        }
+    }
+
+    // numeric equality, under which 1, 1.0 and 2/2 are all the same number
+    pure fn numeric_eq(&self, other:&Expression) -> bool {
+        if self.is_real() && other.is_real() {
+            self.compare_real(other) == Some(0)
+        } else if self.is_number() && other.is_number() {
+            self.to_complex() == other.to_complex()
+        } else {
+            false
+        }
+    }
+
     pure fn to_float(&self) -> float {
         match *self {
             Int( number ) => number as float,
+            Big( ref number ) => number.to_float(),
+            Rational( ref number ) => number.to_float(),
             Float( number ) => number,
             _ => float::NaN
         }
@@ -103,25 +403,389 @@ pub impl Expression {
         }
     }
 
+    // the write form, with datum labels for pairs and vectors that are shared or contain themselves
     pure fn to_str(&self) -> ~str {
-        match copy *self {
+        unsafe { Printer::new(self, Write).print(self) }
+    }
+
+    // the write form without datum labels, which never finishes for cyclic data
+    pure fn to_simple_str(&self) -> ~str {
+        unsafe { Printer::new(self, WriteSimple).print(self) }
+    }
+
+    pure fn to_display_str(&self) -> ~str {
+        unsafe { Printer::new(self, Display).print(self) }
+    }
+
+    // true when some pair or vector is reached more than once, which only datum labels can show
+    pure fn has_shared_structure(&self) -> bool {
+        unsafe { Printer::new(self, Write).shared.len() > 0 }
+    }
+}
+
+enum Style {
+    Write,
+    WriteSimple,
+    Display
+}
+
+// where a pair, vector, bytevector or record lives, which is what makes it the same object as another
+pub fn cell_address( expression:&Expression ) -> Option<uint> {
+    match copy *expression {
+        Pair(cell) => Some(unsafe { cast::reinterpret_cast(&cell) }),
+        Vector(cell) => Some(unsafe { cast::reinterpret_cast(&cell) }),
+        Bytevector(cell) => Some(unsafe { cast::reinterpret_cast(&cell) }),
+        Record(_, values) => Some(unsafe { cast::reinterpret_cast(&values) }),
+        _ => None
+    }
+}
+
+struct Printer {
+    style: Style,
+    // the addresses of cells reached more than once, which get labels
+    mut shared: ~[uint],
+    // the addresses of cells that have been given labels so far, in label order
+    mut labels: ~[uint]
+}
+
+impl Printer {
+    static fn new( expression:&Expression, style:Style ) -> Printer {
+        let printer = Printer { style: style, shared: ~[], labels: ~[] };
+        match style {
+            WriteSimple => (),
+            _ => {
+                let mut seen:LinearMap<uint,()> = LinearMap();
+                printer.find_shared(expression, &mut seen);
+            }
+        }
+        printer
+    }
+
+    // follows cdrs in a loop rather than by recursion, so long lists do not go deep
+    fn find_shared(&self, expression:&Expression, seen:&mut LinearMap<uint,()>) {
+        let mut current = copy *expression;
+        loop {
+            match cell_address(&current) {
+                Some(address) => {
+                    if !seen.insert(address, ()) {
+                        if !vec::contains(self.shared, &address) {
+                            self.shared.push(address);
+                        }
+                        return;
+                    }
+                }
+                None => ()
+            }
+            match copy current {
+                Pair(cell) => {
+                    self.find_shared(&cell.car, seen);
+                    current = copy cell.cdr;
+                }
+                Vector(cell) | Record(_, cell) => {
+                    for (copy *cell).each() |item| { self.find_shared(item, seen) }
+                    return;
+                }
+                Error(condition) => {
+                    for condition.irritants.each() |item| { self.find_shared(item, seen) }
+                    return;
+                }
+                _ => return
+            }
+        }
+    }
+
+    fn is_shared(&self, expression:&Expression) -> bool {
+        match cell_address(expression) {
+            Some(address) => vec::contains(self.shared, &address),
+            None => false
+        }
+    }
+
+    fn print(&self, expression:&Expression) -> ~str {
+        match cell_address(expression) {
+            Some(address) if self.is_shared(expression) => {
+                match vec::position_elem(self.labels, &address) {
+                    Some(label) => fmt!("#%u#", label),
+                    None => {
+                        self.labels.push(address);
+                        fmt!("#%u=", self.labels.len() - 1) + self.print_contents(expression)
+                    }
+                }
+            }
+            _ => self.print_contents(expression)
+        }
+    }
+
+    fn print_all(&self, expressions:&[Expression]) -> ~str {
+        str::connect(expressions.map( | expr | {self.print(expr)} ), ~" ")
+    }
+
+    fn print_contents(&self, expression:&Expression) -> ~str {
+        let display = match self.style { Display => true, _ => false };
+        match copy *expression {
             Bool(value) => if value { ~"#t" } else { ~"#f" },
             Int(number) => { fmt!("%d", number) }
-            Float(number) => { 
-                if number == (number as int) as float {
-                    fmt!("%.1f", number)
-                } else {
-                    fmt!("%f", number)
+            Big(number) => { number.to_str() }
+            Rational(number) => { number.to_str() }
+            Float(number) => { write_float(number) }
+            Complex(real, imaginary) => { write_complex(real, imaginary) }
+            Symbol(string) => if display { string } else { write_symbol(string) },
+            String(string) => if display { string } else { write_string(string) },
+            Char(c) => if display { str::from_char(c) } else { write_char(c) },
+            Error(condition) => {
+                let mut text = ~"#<error " + self.print(&String(copy condition.message));
+                for condition.irritants.each() |irritant| {
+                    text += ~" " + self.print(irritant);
+                }
+                text + ~">"
+            }
+            Nil => ~"()",
+            Unspecified => ~"#<unspecified>",
+            Pair(cell) => {
+                // a shared pair in the middle of the list needs its label, so it is
+                // written as a dotted tail
+                let mut text = ~"(" + self.print(&cell.car);
+                let mut rest = copy cell.cdr;
+                loop {
+                    match copy rest {
+                        Nil => break,
+                        Pair(next) if !self.is_shared(&rest) => {
+                            text += ~" " + self.print(&next.car);
+                            rest = copy next.cdr;
+                        }
+                        _ => {
+                            text += ~" . " + self.print(&rest);
+                            break;
+                        }
+                    }
+                }
+                text + ~")"
+            }
+            Vector(items) => { ~"#(" + self.print_all(copy *items) + ~")" }
+            Bytevector(bytes) => {
+                let strings = (copy *bytes).map( | &byte | {fmt!("%u", byte as uint)} );
+                ~"#u8(" + str::connect(strings, ~" ") + ~")"
+            }
+            HashTable(_) => { ~"#<hash-table>" }
+            Record(record_type, values) => {
+                let mut text = ~"#<record " + record_type.display_name();
+                for record_type.fields.eachi() |i, field| {
+                    text += fmt!(" %s=%s", *field, self.print(&(*values)[i]));
+                }
+                text + ~">"
+            }
+            Proc(_,_,name) => { fmt!("#<procedure %s>", name) }
+            Lambda(_,_,_,Some(name)) => { fmt!("#<procedure %s>", name) }
+            Lambda(_,_,_,None) => { ~"#<procedure>" }
+            RecordProcedure(_,_,name) => { fmt!("#<procedure %s>", name) }
+        }
+    }
+}
+
+pure fn write_string( string:&str ) -> ~str {
+    let mut result = ~"\"";
+    for str::each_char(string) |c| {
+        result += match c {
+            '"' => ~"\\\"",
+            '\\' => ~"\\\\",
+            '\n' => ~"\\n",
+            '\t' => ~"\\t",
+            '\r' => ~"\\r",
+            c if (c as uint) < 0x20 || c == '\x7f' => fmt!("\\x%x;", c as uint),
+            c => str::from_char(c)
+        };
+    }
+    result + ~"\""
+}
+
+// magnitude / 10^power without 10^power underflowing for the smallest floats
+pure fn shift_decimal( magnitude:float, power:int ) -> float {
+    if power < -300 {
+        magnitude * float::pow(10.0, 300.0) / float::pow(10.0, (power + 300) as float)
+    } else {
+        magnitude / float::pow(10.0, power as float)
+    }
+}
+
+// the digits of the number rounded to precision significant figures, and the power of
+// ten of the first one; the division may be off in the last place, so callers check
+// the result by reading it back
+pure fn significant_digits( magnitude:float, precision:uint ) -> (u64, int) {
+    let mut exponent = float::floor(float::log10(magnitude)) as int;
+    let limit = float::pow(10.0, precision as float) as u64;
+    let mut digits = float::round(shift_decimal(magnitude, exponent - precision as int + 1)) as u64;
+    if digits >= limit {
+        // rounding carried into another digit, or log10 came out one too small
+        exponent += 1;
+    } else if digits < limit / 10 {
+        exponent -= 1;
+    }
+    digits = float::round(shift_decimal(magnitude, exponent - precision as int + 1)) as u64;
+    (digits, exponent)
+}
+
+// digits with the point placed exponent places after the first one, switching to
+// scientific notation for very large and very small numbers
+pure fn place_point( digits:u64, exponent:int ) -> ~str {
+    let mut text = u64::str(digits);
+    while text.len() > 1 && str::ends_with(text, "0") {
+        text = str::slice(text, 0, text.len() - 1);
+    }
+    if exponent >= 21 || exponent < -7 {
+        let mantissa = if text.len() == 1 {
+            copy text
+        } else {
+            str::slice(text, 0, 1) + ~"." + str::slice(text, 1, text.len())
+        };
+        fmt!("%se%d", mantissa, exponent)
+    } else if exponent < 0 {
+        ~"0." + str::repeat("0", (-exponent - 1) as uint) + text
+    } else {
+        let point = (exponent + 1) as uint;
+        if text.len() <= point {
+            text + str::repeat("0", point - text.len()) + ~".0"
+        } else {
+            str::slice(text, 0, point) + ~"." + str::slice(text, point, text.len())
+        }
+    }
+}
+
+// the shortest decimal that reads back as exactly this float
+pure fn write_float( number:float ) -> ~str {
+    if float::is_NaN(number) {
+        return ~"+nan.0";
+    }
+    if float::is_infinite(number) {
+        return if number > 0.0 { ~"+inf.0" } else { ~"-inf.0" };
+    }
+    if number == 0.0 {
+        return if 1.0 / number < 0.0 { ~"-0.0" } else { ~"0.0" };
+    }
+    let sign = if number < 0.0 { ~"-" } else { ~"" };
+    let magnitude = float::abs(number);
+    // seventeen significant digits are always enough to tell two floats apart
+    for uint::range(1, 18) |precision| {
+        let (digits, exponent) = significant_digits(magnitude, precision);
+        for [digits, digits - 1, digits + 1].each |&candidate| {
+            let text = sign + place_point(candidate, exponent);
+            if float::from_str(text) == Some(number) {
+                return text;
+            }
+        }
+    }
+    let (digits, exponent) = significant_digits(magnitude, 17);
+    sign + place_point(digits, exponent)
+}
+
+// a+bi, with the sign of the imaginary part always written
+pure fn write_complex( real:float, imaginary:float ) -> ~str {
+    let imaginary = write_float(imaginary);
+    let sign = if str::starts_with(imaginary, "-") || str::starts_with(imaginary, "+") { ~"" } else { ~"+" };
+    write_float(real) + sign + imaginary + ~"i"
+}
+
+// true when the reader would not give this name back as a symbol without |bars|
+pure fn needs_bars( name:&str ) -> bool {
+    let chars = str::chars(name);
+    if chars.len() == 0 || chars[0] == '#' || name == "." {
+        return true;
+    }
+    for chars.each() |&c| {
+        match c {
+            '(' | ')' | '[' | ']' | '"' | ';' | '|' | '\'' | '`' | ',' | '\\' => return true,
+            c if char::is_whitespace(c) || (c as uint) < 0x20 || c == '\x7f' => return true,
+            _ => ()
+        }
+    }
+    pure fn digit_at( chars:&[char], i:uint ) -> bool {
+        i < chars.len() && char::is_digit(chars[i])
+    }
+    // anything that starts out like a number is read as one, or rejected as a malformed one
+    if digit_at(chars, 0) {
+        return true;
+    }
+    if chars[0] == '+' || chars[0] == '-' {
+        if digit_at(chars, 1) || (chars.len() > 1 && chars[1] == '.' && digit_at(chars, 2)) {
+            return true;
+        }
+        let rest = str::to_lower(str::slice(name, 1, name.len()));
+        if rest == ~"inf.0" || rest == ~"nan.0" || rest == ~"i" || rest == ~"inf.0i" || rest == ~"nan.0i" {
+            return true;
+        }
+    }
+    chars[0] == '.' && digit_at(chars, 1)
+}
+
+pure fn write_symbol( name:&str ) -> ~str {
+    if !needs_bars(name) {
+        return str::from_slice(name);
+    }
+    let mut result = ~"|";
+    for str::each_char(name) |c| {
+        result += match c {
+            '|' => ~"\\|",
+            '\\' => ~"\\\\",
+            c if (c as uint) < 0x20 || c == '\x7f' => fmt!("\\x%x;", c as uint),
+            c => str::from_char(c)
+        };
+    }
+    result + ~"|"
+}
+
+pure fn write_char( c:char ) -> ~str {
+    match c {
+        ' ' => ~"#\\space",
+        '\n' => ~"#\\newline",
+        '\t' => ~"#\\tab",
+        '\r' => ~"#\\return",
+        '\x00' => ~"#\\nul",
+        '\x07' => ~"#\\alarm",
+        '\x08' => ~"#\\backspace",
+        '\x1b' => ~"#\\escape",
+        '\x7f' => ~"#\\delete",
+        c if (c as uint) < 0x20 => fmt!("#\\x%x", c as uint),
+        c => ~"#\\" + str::from_char(c)
+    }
+}
+
+// equal? on pairs and vectors, which may share cells or refer back to themselves.
+// Two cells already being compared further up are taken to be equal, so cyclic values
+// that unfold the same way come out equal instead of being compared forever
+fn equal_structure( a:&Expression, b:&Expression, comparing:&mut LinearMap<(uint,uint),()> ) -> bool {
+    let mut a = copy *a;
+    let mut b = copy *b;
+    // follows cdrs in a loop rather than by recursion, so long lists do not go deep
+    loop {
+        let both_cells = match (copy a, copy b) {
+            (Pair(_), Pair(_)) | (Vector(_), Vector(_)) => true,
+            _ => false
+        };
+        if both_cells {
+            let addresses = (cell_address(&a).get(), cell_address(&b).get());
+            if addresses.first() == addresses.second() || !comparing.insert(addresses, ()) {
+                return true;
+            }
+        }
+        match (copy a, copy b) {
+            (Pair(x), Pair(y)) => {
+                if !equal_structure(&x.car, &y.car, comparing) {
+                    return false;
                 }
+                a = copy x.cdr;
+                b = copy y.cdr;
             }
-            Symbol(string) => { copy string }
-            Error(string) => { fmt!("Error: %s", string) }
-            List(expressions) => {
-                let strings = expressions.map( | &expr | {expr.to_str()} );
-                ~"(" + strings.foldl(~"", |&x, &y| { x + ~" " + y } ).trim() + ~")"
+            (Vector(x), Vector(y)) => {
+                if x.len() != y.len() {
+                    return false;
+                }
+                for uint::range(0, x.len()) |i| {
+                    if !equal_structure(&(*x)[i], &(*y)[i], comparing) {
+                        return false;
+                    }
+                }
+                return true;
             }
-            Proc(_,x) => { fmt!("procedure: %s", x.to_str()) }
-            Lambda(_,_,_) => {~"Lambda"}
+            _ => return a == b
         }
     }
 }
@@ -131,13 +795,30 @@ impl Expression : cmp::Eq {
         match copy *self {
             Bool(x) => match *other { Bool(y) => x ==y, _ => false },
             Int(x) => match *other { Int(y) => x == y, _ => false },
+            Big(x) => match copy *other { Big(y) => x == y, _ => false },
+            Rational(x) => match copy *other { Rational(y) => x == y, _ => false },
             Float(x) => match *other { Float(y) => x == y, _ => false },
+            Complex(a, b) => match *other { Complex(c, d) => a == c && b == d, _ => false },
             Symbol(x) => match copy *other { Symbol(y) => x == y, _ => false },
-            List(x) => match copy *other { List(y) => x == y, _ => false },
-            Proc(_,x) => match copy *other { Proc(_,y) => x == y, _=> false },
-            Lambda(a,b,c) => { 
+            String(x) => match copy *other { String(y) => x == y, _ => false },
+            Char(x) => match *other { Char(y) => x == y, _ => false },
+            Nil => match *other { Nil => true, _ => false },
+            Unspecified => match *other { Unspecified => true, _ => false },
+            Pair(_) => match *other {
+                Pair(_) => unsafe { equal_structure(self, other, &mut LinearMap()) },
+                _ => false
+            },
+            Vector(_) => match *other {
+                Vector(_) => unsafe { equal_structure(self, other, &mut LinearMap()) },
+                _ => false
+            },
+            Bytevector(x) => match copy *other { Bytevector(y) => copy *x == copy *y, _ => false },
+            HashTable(x) => match copy *other { HashTable(y) => managed::mut_ptr_eq(x, y), _ => false },
+            Record(_, x) => match copy *other { Record(_, y) => managed::mut_ptr_eq(x, y), _ => false },
+            Proc(_,x,_) => match copy *other { Proc(_,y,_) => x == y, _=> false },
+            Lambda(a,b,c,_) => { 
                 match copy *other { 
-                    Lambda(x,y,z) => unsafe {
+                    Lambda(x,y,z,_) => unsafe {
                         ptr::ref_eq(a,x) &&
                         b == y &&
                         ptr::ref_eq(c,z)
@@ -145,7 +826,11 @@ impl Expression : cmp::Eq {
                     _ => false
                 }
             }
-            Error(_) => false
+            RecordProcedure(x, a, _) => match copy *other {
+                RecordProcedure(y, b, _) => managed::ptr_eq(x, y) && a == b,
+                _ => false
+            },
+            Error(x) => match copy *other { Error(y) => managed::ptr_eq(x, y), _ => false }
         }
     }
 
@@ -159,6 +844,104 @@ fn test_that_adding_non_numbers_gives_an_error() {
     assert (Int(1) + Symbol(~"a")).is_error()
 }
 
+#[test]
+fn test_that_errors_print_their_message_and_irritants() {
+    assert (Int(1) + Symbol(~"a")).to_str() == ~"#<error \"Expected a number\" a>";
+    assert Expression::new_error(UserCondition, ~"Gone wrong", ~[]).to_str() == ~"#<error \"Gone wrong\">";
+    let irritants = ~[String(~"b"), Expression::new_list(~[Int(1)])];
+    let error = Expression::new_error(UserCondition, ~"Gone wrong", irritants);
+    assert error.to_str() == ~"#<error \"Gone wrong\" \"b\" (1)>";
+    assert error.to_display_str() == ~"#<error Gone wrong b (1)>";
+}
+
+#[test]
+fn test_that_int_arithmetic_promotes_to_big_on_overflow() {
+    let max = Int(int::max_value);
+    match max + Int(1) {
+        Big(n) => assert n == BigInt::from_int(int::max_value) + BigInt::from_int(1),
+        other => fail fmt!("max_int + 1 gave %s", other.to_str())
+    }
+    assert (Int(int::min_value) - Int(1)).to_str() == ~"-9223372036854775809";
+    assert (Int(99999999999) * Int(99999999999)).to_str() == ~"9999999999800000000001";
+    assert (Int(int::min_value) / Int(-1)).to_str() == ~"9223372036854775808";
+}
+
+#[test]
+fn test_that_big_results_that_fit_become_ints_again() {
+    let big = Int(int::max_value) + Int(1);
+    assert big - Int(1) == Int(int::max_value);
+    assert (Int(99999999999) * Int(99999999999)) / Int(99999999999) == Int(99999999999);
+}
+
+#[test]
+fn test_that_big_integers_compare_with_other_numbers() {
+    let big = Int(int::max_value) * Int(2);
+    assert big > Int(int::max_value);
+    assert Int(int::min_value) > Int(int::min_value) * Int(2);
+    assert big < Float(1e30);
+    assert big == Int(int::max_value) + Int(int::max_value);
+}
+
+#[test]
+fn test_that_dividing_integers_gives_exact_fractions() {
+    assert (Int(1) / Int(3)).to_str() == ~"1/3";
+    assert (Int(7) / Int(-2)).to_str() == ~"-7/2";
+    assert Int(6) / Int(3) == Int(2);
+    assert (Int(1) / Int(3)) * Int(3) == Int(1);
+    assert (Int(1) / Int(2)) + (Int(1) / Int(3)) == Int(5) / Int(6);
+    assert (Int(1) / Int(2)) + Float(0.25) == Float(0.75);
+    assert (Int(1) / Int(0)).is_error();
+}
+
+#[test]
+fn test_that_fractions_compare_with_other_numbers() {
+    let third = Int(1) / Int(3);
+    assert third < Int(1) / Int(2);
+    assert third > Int(0);
+    assert third < Float(0.34);
+    assert Int(-1) / Int(2) <= Int(-1) / Int(2);
+}
+
+#[test]
+fn test_that_numbers_are_equal_across_exactness() {
+    assert Int(1).numeric_eq(&Float(1.0));
+    assert (Int(1) / Int(2)).numeric_eq(&Float(0.5));
+    assert (Int(int::max_value) + Int(1)).numeric_eq(&Float(9223372036854775808.0));
+    assert !Int(9007199254740993).numeric_eq(&Float(9007199254740992.0));
+    assert !Float(float::NaN).numeric_eq(&Float(float::NaN));
+    assert !Int(1).numeric_eq(&Symbol(~"a"));
+    assert Int(1) != Float(1.0);
+}
+
+#[test]
+fn test_that_exact_numbers_and_floats_compare_exactly() {
+    assert Int(9007199254740993) > Float(9007199254740992.0);
+    assert Int(1) / Int(3) > Float(0.3333333333333333);
+    assert Int(int::max_value) < Float(float::infinity);
+    assert !(Int(1) < Float(float::NaN)) && !(Int(1) >= Float(float::NaN));
+}
+
+#[test]
+fn test_that_complex_arithmetic_works_on_both_parts() {
+    let i = Complex(0.0, 1.0);
+    assert i * i == Float(-1.0);
+    assert Int(3) + Complex(0.0, 4.0) == Complex(3.0, 4.0);
+    assert Complex(1.0, 2.0) * Complex(3.0, -1.0) == Complex(5.0, 5.0);
+    assert Complex(5.0, 5.0) / Complex(3.0, -1.0) == Complex(1.0, 2.0);
+    assert Complex(1.0, 2.0) - Complex(1.0, 2.0) == Float(0.0);
+    assert Complex(1.0, 2.0).numeric_eq(&Complex(1.0, 2.0));
+    assert !(Complex(1.0, 2.0) < Complex(2.0, 2.0));
+}
+
+#[test]
+fn test_that_complex_numbers_are_written_as_a_plus_bi() {
+    assert Complex(3.0, 4.0).to_str() == ~"3.0+4.0i";
+    assert Complex(0.5, -1.0).to_str() == ~"0.5-1.0i";
+    assert Complex(0.0, float::infinity).to_str() == ~"0.0+inf.0i";
+    assert Expression::new_rectangular(&Int(3), &Int(0)) == Int(3);
+    assert Expression::new_polar(&Int(2), &Int(0)) == Int(2);
+}
+
 #[test]
 fn test_to_bool_returns_true_for_non_zero_numbers() {
     assert Int(1).to_bool();
@@ -193,6 +976,241 @@ fn test_that_symbols_are_comparable() {
 
 #[test]
 fn test_that_vectors_are_comparable() {
-    assert List(~[Int(1)]) == List(~[Int(1)]);
-    assert List(~[Int(1)]) != List(~[Int(2)]);
+    assert Expression::new_list(~[Int(1)]) == Expression::new_list(~[Int(1)]);
+    assert Expression::new_list(~[Int(1)]) != Expression::new_list(~[Int(2)]);
+    assert Expression::new_list(~[Int(1)]) != Expression::new_list(~[Int(1), Int(2)]);
+    assert Nil == Nil;
+}
+
+#[test]
+fn test_that_strings_are_comparable() {
+    assert String(~"a") == String(~"a");
+    assert String(~"a") != String(~"b");
+    assert String(~"a") != Symbol(~"a");
+}
+
+#[test]
+fn test_that_strings_are_written_with_quotes_and_escapes() {
+    assert String(~"hello").to_str() == ~"\"hello\"";
+    assert String(~"a\"b\\c\nd").to_str() == ~"\"a\\\"b\\\\c\\nd\"";
+    assert String(~"\x01").to_str() == ~"\"\\x1;\"";
+}
+
+#[test]
+fn test_that_strings_are_displayed_without_quotes() {
+    assert String(~"hello").to_display_str() == ~"hello";
+    assert Expression::new_list(~[String(~"a"), Int(1)]).to_display_str() == ~"(a 1)";
+    assert Expression::new_list(~[String(~"a"), Int(1)]).to_str() == ~"(\"a\" 1)";
+}
+
+#[test]
+fn test_that_characters_are_comparable() {
+    assert Char('a') == Char('a');
+    assert Char('a') != Char('b');
+    assert Char('a') != String(~"a");
+}
+
+#[test]
+fn test_that_characters_are_written_as_literals() {
+    assert Char('a').to_str() == ~"#\\a";
+    assert Char(' ').to_str() == ~"#\\space";
+    assert Char('\n').to_str() == ~"#\\newline";
+    assert Char('\x01').to_str() == ~"#\\x1";
+    assert Char('\u03bb').to_str() == ~"#\\\u03bb";
+    assert Char('a').to_display_str() == ~"a";
+}
+
+#[test]
+fn test_that_dotted_lists_are_comparable() {
+    assert Expression::new_pair(Int(1), Int(2)) == Expression::new_pair(Int(1), Int(2));
+    assert Expression::new_pair(Int(1), Int(2)) != Expression::new_pair(Int(1), Int(3));
+    assert Expression::new_pair(Int(1), Int(2)) != Expression::new_list(~[Int(1), Int(2)]);
+}
+
+#[test]
+fn test_that_dotted_lists_print_their_tails() {
+    assert Expression::new_pair(Symbol(~"a"), Symbol(~"b")).to_str() == ~"(a . b)";
+    assert Expression::new_dotted(~[Symbol(~"a"), Symbol(~"b")], Symbol(~"c")).to_str() == ~"(a b . c)";
+    assert Expression::new_pair(String(~"a"), String(~"b")).to_display_str() == ~"(a . b)";
+}
+
+#[test]
+fn test_that_new_dotted_chains_pairs_onto_the_tail() {
+    assert Expression::new_dotted(~[Int(1)], Int(2)) == Expression::new_pair(Int(1), Int(2));
+    assert Expression::new_dotted(~[Int(1)], Expression::new_list(~[Int(2)])) == Expression::new_list(~[Int(1), Int(2)]);
+    assert Expression::new_dotted(~[], Int(3)) == Int(3);
+    assert Expression::new_list(~[]) == Nil;
+}
+
+#[test]
+fn test_that_vectors_compare_by_contents() {
+    assert Vector(@mut ~[Int(1)]) == Vector(@mut ~[Int(1)]);
+    assert Vector(@mut ~[Int(1)]) != Vector(@mut ~[Int(2)]);
+    assert Vector(@mut ~[Int(1)]) != Expression::new_list(~[Int(1)]);
+}
+
+// #0=(1 2 . #0#), or with a different second item
+fn cyclic_list( second:int ) -> Expression {
+    let last = @mut Cons { car: Int(second), cdr: Nil };
+    let first = @mut Cons { car: Int(1), cdr: Pair(last) };
+    last.cdr = Pair(first);
+    Pair(first)
+}
+
+#[test]
+fn test_that_cyclic_values_can_be_compared() {
+    let list = cyclic_list(2);
+    assert list == list;
+    assert cyclic_list(2) == cyclic_list(2);
+    assert cyclic_list(2) != cyclic_list(3);
+    assert cyclic_list(2) != Expression::new_list(~[Int(1), Int(2)]);
+    let x = @mut ~[Int(1), Nil];
+    (*x)[1] = Vector(x);
+    let y = @mut ~[Int(1), Nil];
+    (*y)[1] = Vector(y);
+    assert Vector(x) == Vector(x);
+    assert Vector(x) == Vector(y);
+    (*y)[0] = Int(2);
+    assert Vector(x) != Vector(y);
+}
+
+#[test]
+fn test_that_vectors_print_with_a_hash() {
+    assert Vector(@mut ~[]).to_str() == ~"#()";
+    assert Vector(@mut ~[Int(1), String(~"a")]).to_str() == ~"#(1 \"a\")";
+    assert Vector(@mut ~[Int(1), String(~"a")]).to_display_str() == ~"#(1 a)";
+}
+
+#[test]
+fn test_that_infinities_and_nan_print_as_readable_literals() {
+    assert Float(float::infinity).to_str() == ~"+inf.0";
+    assert Float(float::neg_infinity).to_str() == ~"-inf.0";
+    assert Float(float::NaN).to_str() == ~"+nan.0";
+}
+
+#[test]
+fn test_that_bytevectors_compare_by_contents() {
+    assert Bytevector(@mut ~[1u8, 2u8]) == Bytevector(@mut ~[1u8, 2u8]);
+    assert Bytevector(@mut ~[1u8]) != Bytevector(@mut ~[2u8]);
+    assert Bytevector(@mut ~[1u8]) != Vector(@mut ~[Int(1)]);
+}
+
+#[test]
+fn test_that_bytevectors_print_with_u8_prefix() {
+    assert Bytevector(@mut ~[]).to_str() == ~"#u8()";
+    assert Bytevector(@mut ~[0u8, 255u8, 16u8]).to_str() == ~"#u8(0 255 16)";
+}
+
+#[test]
+fn test_that_plain_symbols_print_as_they_are() {
+    for [~"a", ~"list->vector", ~"+", ~"-", ~"...", ~"->x", ~"set!", ~"a.b", ~"+a"].each |&name| {
+        assert Symbol(copy name).to_str() == name;
+    }
+}
+
+#[test]
+fn test_that_symbols_that_would_not_read_back_print_with_bars() {
+    assert Symbol(~"").to_str() == ~"||";
+    assert Symbol(~"hello world").to_str() == ~"|hello world|";
+    assert Symbol(~"1").to_str() == ~"|1|";
+    assert Symbol(~"-2x").to_str() == ~"|-2x|";
+    assert Symbol(~"+.5").to_str() == ~"|+.5|";
+    assert Symbol(~"+inf.0").to_str() == ~"|+inf.0|";
+    assert Symbol(~"-i").to_str() == ~"|-i|";
+    assert Symbol(~"+nan.0i").to_str() == ~"|+nan.0i|";
+    assert Symbol(~".").to_str() == ~"|.|";
+    assert Symbol(~"#foo").to_str() == ~"|#foo|";
+    assert Symbol(~"a|b").to_str() == ~"|a\\|b|";
+    assert Symbol(~"a\\b").to_str() == ~"|a\\\\b|";
+    assert Symbol(~"a\tb").to_str() == ~"|a\\x9;b|";
+}
+
+#[test]
+fn test_that_symbols_display_without_bars() {
+    assert Symbol(~"hello world").to_display_str() == ~"hello world";
+}
+
+#[test]
+fn test_that_floats_print_with_the_fewest_digits_that_read_back() {
+    assert Float(0.1).to_str() == ~"0.1";
+    assert Float(1.0).to_str() == ~"1.0";
+    assert Float(-2.5).to_str() == ~"-2.5";
+    assert Float(1200.0).to_str() == ~"1200.0";
+    assert Float(0.001).to_str() == ~"0.001";
+    assert Float(1.0 / 3.0).to_str() == ~"0.3333333333333333";
+    assert Float(0.1 + 0.2).to_str() == ~"0.30000000000000004";
+    assert Float(1e22).to_str() == ~"1e22";
+    assert Float(1.5e-10).to_str() == ~"1.5e-10";
+    assert Float(-0.0).to_str() == ~"-0.0";
+}
+
+#[test]
+fn test_that_hash_tables_are_only_equal_to_themselves() {
+    let table = HashTable(@mut Table::new(Equal));
+    assert table == copy table;
+    assert table != HashTable(@mut Table::new(Equal));
+    assert table.to_str() == ~"#<hash-table>";
+}
+
+#[test]
+fn test_that_procedures_print_with_their_names() {
+    assert Expression::new_proc(~"car", |_| Int(0)).to_str() == ~"#<procedure car>";
+}
+
+#[test]
+fn test_that_shared_vectors_are_written_with_datum_labels() {
+    let shared = Vector(@mut ~[Int(1)]);
+    let outer = Expression::new_list(~[copy shared, Symbol(~"x"), copy shared]);
+    assert outer.to_str() == ~"(#0=#(1) x #0#)";
+    assert outer.to_simple_str() == ~"(#(1) x #(1))";
+}
+
+#[test]
+fn test_that_cyclic_vectors_are_written_with_datum_labels() {
+    let cell = @mut ~[Int(1)];
+    (*cell).push(Vector(cell));
+    assert Vector(cell).to_str() == ~"#0=#(1 #0#)";
+    assert Vector(cell).to_display_str() == ~"#0=#(1 #0#)";
+}
+
+#[test]
+fn test_that_equal_but_separate_vectors_get_no_labels() {
+    let outer = Expression::new_list(~[Vector(@mut ~[Int(1)]), Vector(@mut ~[Int(1)])]);
+    assert outer.to_str() == ~"(#(1) #(1))";
+}
+
+#[test]
+fn test_that_list_parts_split_off_the_tail() {
+    let (items, tail) = Expression::new_dotted(~[Int(1), Int(2)], Int(3)).list_parts();
+    assert items == ~[Int(1), Int(2)] && tail == Int(3);
+    assert Expression::new_list(~[Int(1), Int(2)]).list_items() == Some(~[Int(1), Int(2)]);
+    assert Nil.list_items() == Some(~[]);
+    assert Expression::new_pair(Int(1), Int(2)).list_items().is_none();
+    assert Int(1).list_items().is_none();
+}
+
+#[test]
+fn test_that_cyclic_lists_are_not_lists() {
+    let cell = @mut Cons { car: Int(1), cdr: Nil };
+    cell.cdr = Expression::new_dotted(~[Int(2), Int(3)], Pair(cell));
+    assert !Pair(cell).is_list();
+    cell.cdr = Pair(cell);
+    assert !Pair(cell).is_list();
+}
+
+#[test]
+fn test_that_shared_tails_are_written_with_datum_labels() {
+    let tail = Expression::new_list(~[Int(2)]);
+    let outer = Expression::new_list(~[Expression::new_pair(Int(1), copy tail), copy tail]);
+    assert outer.to_str() == ~"((1 . #0=(2)) #0#)";
+    assert outer.to_simple_str() == ~"((1 2) (2))";
+}
+
+#[test]
+fn test_that_cyclic_lists_are_written_with_datum_labels() {
+    let cell = @mut Cons { car: Int(1), cdr: Nil };
+    cell.cdr = Expression::new_pair(Int(2), Pair(cell));
+    assert Pair(cell).to_str() == ~"#0=(1 2 . #0#)";
+    cell.car = Pair(cell);
+    assert Pair(cell).to_str() == ~"#0=(#0# 2 . #0#)";
 }
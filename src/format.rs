@@ -0,0 +1,333 @@
+/*
+ * Rewrites Scheme source into one canonical layout, for `rusty fmt`
+ *
+ * Atoms keep their original spelling and comments are kept where they were
+ * written, either on a line of their own or at the end of the line they
+ * followed. Everything else is decided by the structure of the code alone, so
+ * formatting already formatted source changes nothing:
+ *
+ *  - a list that fits on the rest of its line, and has no comments inside, is
+ *    written on one line with single spaces
+ *  - otherwise it is broken up using the same rules as the pretty printer
+ *  - top-level forms each start on a new line, with at most one blank line
+ *    kept between them
+ */
+
+use cst::{Node,Trivia,Directive,Atom,Compound,Prefixed,parse_cst,lower};
+use parse::{Token,ParseError};
+use pretty::{Body,Conditional,Aligned,indentation};
+
+enum Item {
+    Datum(Node),
+    // a comment, and whether it was written on the same line as the item before it
+    Comment(Token, bool)
+}
+
+fn is_comment( token:&Token ) -> bool {
+    str::starts_with(token.text, ";") || str::starts_with(token.text, "#|")
+}
+
+fn spaces( count:uint ) -> ~str {
+    str::repeat(" ", count)
+}
+
+fn fits( text:&str, column:uint, trailing:uint, width:uint ) -> bool {
+    !str::contains_char(text, '\n') && column + str::char_len(text) + trailing <= width
+}
+
+// the data and comments among nodes, without the whitespace between them
+fn items( nodes:&[Node] ) -> ~[Item] {
+    let mut result:~[Item] = ~[];
+    let mut same_line = false;
+    for nodes.each() |node| {
+        match copy *node {
+            Trivia(token) => {
+                if is_comment(&token) {
+                    result.push(Comment(copy token, same_line && result.len() > 0));
+                    same_line = true;
+                } else if str::contains_char(token.text, '\n') {
+                    same_line = false;
+                }
+            }
+            datum => {
+                result.push(Datum(datum));
+                same_line = true;
+            }
+        }
+    }
+    result
+}
+
+// the node on one line, or None if it holds a comment
+fn flat( node:&Node ) -> Option<~str> {
+    match copy *node {
+        Trivia(token) => if is_comment(&token) { None } else { Some(~"") },
+        Directive(token) | Atom(token) => Some(copy token.text),
+        Compound(open, contents, close) => {
+            let mut parts:~[~str] = ~[];
+            for items(contents).each() |item| {
+                match copy *item {
+                    Datum(child) => match flat(&child) {
+                        Some(text) => parts.push(text),
+                        None => return None
+                    },
+                    Comment(_, _) => return None
+                }
+            }
+            Some(open.text + str::connect(parts, ~" ") + close.text)
+        }
+        Prefixed(prefix, gap, datum) => {
+            if items(gap).len() > 0 {
+                return None;
+            }
+            match flat(datum) {
+                Some(text) => Some(prefix.text + text),
+                None => None
+            }
+        }
+    }
+}
+
+// writes items one to a line at column, except that comments written at the end of a
+// line stay there; first is what goes before the first item when it is not such a
+// comment. Also says whether the last item was a comment, which nothing can follow
+// on the same line.
+fn stack( items:&[Item], column:uint, trailing:uint, width:uint, first:&str ) -> (~str, bool) {
+    let mut text = ~"";
+    let mut ends_with_comment = false;
+    for items.eachi() |i, item| {
+        let separator = if i == 0 { str::from_slice(first) } else { ~"\n" + spaces(column) };
+        match copy *item {
+            Comment(token, true) => {
+                text += ~" " + token.text;
+                ends_with_comment = true;
+            }
+            Comment(token, false) => {
+                text += separator + token.text;
+                ends_with_comment = true;
+            }
+            Datum(node) => {
+                let after = if i + 1 == items.len() { trailing } else { 0 };
+                text += separator + format_node(&node, column, after, width);
+                ends_with_comment = false;
+            }
+        }
+    }
+    (text, ends_with_comment)
+}
+
+// the symbol a list starts with, if any
+fn head_name( items:&[Item] ) -> Option<~str> {
+    match copy items[0] {
+        Datum(Atom(token)) => {
+            let c = str::char_at(token.text, 0);
+            if c == '"' || c == '#' || c == '.' || char::is_digit(c) || is_signed_number(token.text) {
+                None
+            } else {
+                Some(copy token.text)
+            }
+        }
+        _ => None
+    }
+}
+
+// +5 and -.5 are numbers, but + and -> are symbols
+fn is_signed_number( text:&str ) -> bool {
+    let chars = str::chars(text);
+    (chars[0] == '+' || chars[0] == '-') && chars.len() > 1 &&
+        (char::is_digit(chars[1]) || chars[1] == '.')
+}
+
+fn format_compound( open:&Token, contents:&[Node], close:&Token, column:uint, trailing:uint, width:uint ) -> ~str {
+    let items = items(contents);
+    if items.len() == 0 {
+        return open.text + close.text;
+    }
+    let inner = column + str::char_len(open.text);
+    let is_list = open.text == ~"(" || open.text == ~"[";
+    let (body, ends_with_comment) = match head_name(items) {
+        Some(name) if is_list => {
+            let after_head = inner + str::char_len(name) + 1;
+            let named = items.len() > 1 && head_name(vec::slice(items, 1, items.len())).is_some();
+            match indentation(name, named) {
+                Body(count) => {
+                    // the distinguished arguments, up to the first comment
+                    let mut split = 1;
+                    while split < items.len() && split <= count {
+                        match items[split] {
+                            Datum(_) => split += 1,
+                            Comment(_, _) => break
+                        }
+                    }
+                    let has_body = split < items.len();
+                    let after = if has_body { 0 } else { trailing + 1 };
+                    let arguments = vec::slice(items, 1, split);
+                    let flat_arguments = arguments.map(|item| match copy *item {
+                        Datum(node) => flat(&node),
+                        Comment(_, _) => None
+                    });
+                    let mut text = copy name;
+                    if arguments.len() > 0 {
+                        let joined = if vec::all(flat_arguments, |text| text.is_some()) {
+                            Some(str::connect(flat_arguments.map(|text| text.get()), ~" "))
+                        } else {
+                            None
+                        };
+                        match joined {
+                            Some(joined) if fits(joined, after_head, after, width) => text += ~" " + joined,
+                            _ => {
+                                let (stacked, _) = stack(arguments, after_head, after, width, ~" ");
+                                text += stacked;
+                            }
+                        }
+                    }
+                    let (rest, ends_with_comment) = stack(vec::slice(items, split, items.len()), column + 2, trailing + 1, width, ~"\n" + spaces(column + 2));
+                    (text + rest, ends_with_comment)
+                }
+                Conditional => {
+                    if items.len() > 1 {
+                        let (rest, ends_with_comment) = stack(vec::slice(items, 1, items.len()), after_head, trailing + 1, width, ~" ");
+                        (name + rest, ends_with_comment)
+                    } else {
+                        stack(items, inner, trailing + 1, width, ~"")
+                    }
+                }
+                Aligned => {
+                    if items.len() > 1 && after_head <= width / 2 {
+                        let (rest, ends_with_comment) = stack(vec::slice(items, 1, items.len()), after_head, trailing + 1, width, ~" ");
+                        (name + rest, ends_with_comment)
+                    } else {
+                        stack(items, inner, trailing + 1, width, ~"")
+                    }
+                }
+            }
+        }
+        _ => stack(items, inner, trailing + 1, width, ~"")
+    };
+    if ends_with_comment {
+        open.text + body + ~"\n" + spaces(inner) + close.text
+    } else {
+        open.text + body + close.text
+    }
+}
+
+// the text of node when it starts at column and is followed by trailing closing
+// parentheses, with later lines indented to match
+fn format_node( node:&Node, column:uint, trailing:uint, width:uint ) -> ~str {
+    match flat(node) {
+        Some(text) if fits(text, column, trailing, width) => return text,
+        _ => ()
+    }
+    match copy *node {
+        Compound(open, contents, close) => format_compound(&open, contents, &close, column, trailing, width),
+        Prefixed(prefix, gap, datum) => {
+            let inner = column + str::char_len(prefix.text);
+            let mut text = copy prefix.text;
+            for items(gap).each() |item| {
+                match copy *item {
+                    Comment(token, _) => text += token.text + ~"\n" + spaces(inner),
+                    Datum(_) => ()
+                }
+            }
+            text + format_node(datum, inner, trailing, width)
+        }
+        Trivia(token) | Directive(token) | Atom(token) => copy token.text
+    }
+}
+
+// program laid out to fit width where it can be, ending with a single newline
+pub fn format_source( program:&str, width:uint ) -> Result<~str, ParseError> {
+    let nodes = match parse_cst( program ) {
+        Ok(nodes) => nodes,
+        Err(error) => return Err(error)
+    };
+    // only format what the reader would accept
+    match lower( nodes ) {
+        Ok(_) => (),
+        Err(error) => return Err(error)
+    }
+    let mut text = ~"";
+    let mut newlines = 0u;
+    let mut first = true;
+    for nodes.each() |node| {
+        let separator = if first { ~"" } else if newlines > 1 { ~"\n\n" } else { ~"\n" };
+        match copy *node {
+            Trivia(token) if !is_comment(&token) => {
+                newlines += str::count_chars(token.text, 0, token.text.len(), '\n');
+            }
+            other => {
+                text += match other {
+                    Trivia(token) if !first && newlines == 0 => ~" " + token.text,
+                    Trivia(token) => separator + token.text,
+                    datum => separator + format_node(&datum, 0, 0, width)
+                };
+                newlines = 0;
+                first = false;
+            }
+        }
+    }
+    if first { Ok(~"") } else { Ok(text + ~"\n") }
+}
+
+fn format_ok( program:&str, width:uint ) -> ~str {
+    match format_source( program, width ) {
+        Ok(text) => text,
+        Err(error) => fail fmt!("Could not format %s: %s", program, error.to_str())
+    }
+}
+
+#[test]
+fn test_that_short_forms_go_on_one_line() {
+    assert format_ok( ~"(define   (square x)\n    (* x   x))", 80 ) == ~"(define (square x) (* x x))\n";
+}
+
+#[test]
+fn test_that_long_forms_are_broken_by_their_shape() {
+    assert format_ok( ~"(define (f x) (if (null? x) (quote ()) (car x)))", 24 ) ==
+        ~"(define (f x)\n  (if (null? x)\n      (quote ())\n      (car x)))\n";
+}
+
+#[test]
+fn test_that_if_keeps_its_test_on_the_first_line_when_far_over() {
+    assert format_ok( ~"(foo-long-name (if (null? xs) (quote ()) (car xs)))", 30 ) ==
+        ~"(foo-long-name (if (null? xs)\n" + spaces(19) + ~"(quote ())\n" + spaces(19) + ~"(car xs)))\n";
+}
+
+#[test]
+fn test_that_atoms_keep_their_spelling() {
+    assert format_ok( ~"(list   #xFF  1.50e1 #\\space |a b| \"x\\ty\")", 80 ) ==
+        ~"(list #xFF 1.50e1 #\\space |a b| \"x\\ty\")\n";
+}
+
+#[test]
+fn test_that_comments_are_kept_in_place() {
+    let program = ~";; header\n(define (f x) ; trailing\n  ;; own line\n  (g x))\n";
+    assert format_ok( program, 80 ) == program;
+}
+
+#[test]
+fn test_that_a_comment_at_the_end_of_a_list_pushes_the_paren_down() {
+    assert format_ok( ~"(a b ; note\n)", 80 ) == ~"(a b ; note\n )\n";
+}
+
+#[test]
+fn test_that_one_blank_line_is_kept_between_top_level_forms() {
+    assert format_ok( ~"\n\n(a)\n\n\n\n(b) (c)\n#| block |#\n", 80 ) == ~"(a)\n\n(b)\n(c)\n#| block |#\n";
+}
+
+#[test]
+fn test_that_formatting_is_idempotent() {
+    let program = ~"#!fold-case\n(define (riff-shuffle deck) ; shuffle\n (begin (define take (lambda (n seq) (if (<= n 0) (quote ()) (cons (car seq) (take (- n 1) (cdr seq)))))) #;(unused)\n ((combine append) (take (mid deck) deck) ; first half\n ; then\n (drop (mid deck) deck)) '#(1 2 3) `(a ,b ,@c)))";
+    for [20u, 40u, 80u].each |&width| {
+        let once = format_ok( program, width );
+        assert format_ok( once, width ) == once;
+        assert lower( parse_cst( once ).get() ).get().map(|e| e.to_str()) ==
+            parse_all( program ).get().map(|e| e.to_str());
+    }
+}
+
+#[test]
+fn test_that_unreadable_source_is_not_formatted() {
+    assert format_source( ~"(a", 80 ).is_err();
+    assert format_source( ~"(a #q)", 80 ).is_err();
+}
@@ -0,0 +1,235 @@
+/*
+ * Exact rational numbers
+ *
+ * A Fraction is always in lowest terms with a positive denominator, so like
+ * BigInt every value has one representation. Expression only uses one for
+ * values that are not integers; whole numbers stay Ints or Bigs.
+ */
+
+pub struct Fraction {
+    numerator: BigInt,
+    denominator: BigInt
+}
+
+pub impl Fraction {
+    // fails for a zero denominator, which callers check for first
+    static pure fn new( numerator:BigInt, denominator:BigInt ) -> Fraction {
+        if denominator.is_zero() {
+            fail ~"Fraction with a zero denominator";
+        }
+        let divisor = numerator.gcd(&denominator);
+        let (numerator, _) = numerator.divide(&divisor);
+        let (denominator, _) = denominator.divide(&divisor);
+        if denominator.is_negative() {
+            Fraction { numerator: -numerator, denominator: -denominator }
+        } else {
+            Fraction { numerator: numerator, denominator: denominator }
+        }
+    }
+
+    static pure fn from_integer( n:BigInt ) -> Fraction {
+        Fraction { numerator: n, denominator: BigInt::from_int(1) }
+    }
+
+    // the exact value of a finite float, which always has a power of two below it
+    static pure fn from_float( f:float ) -> Fraction {
+        let mut scaled = f;
+        let mut exponent = 0u;
+        while scaled != float::floor(scaled) {
+            scaled *= 2.0;
+            exponent += 1;
+        }
+        Fraction::new(BigInt::from_float(scaled), BigInt::power_of_two(exponent))
+    }
+
+    pure fn is_integer(&self) -> bool {
+        self.denominator == BigInt::from_int(1)
+    }
+
+    pure fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    // the largest integer no greater than the fraction
+    pure fn floor(&self) -> BigInt {
+        let (quotient, remainder) = self.numerator.divide(&self.denominator);
+        if remainder.is_negative() {
+            quotient - BigInt::from_int(1)
+        } else {
+            quotient
+        }
+    }
+
+    pure fn to_float(&self) -> float {
+        // both convert exactly below 2^53, so one division rounds correctly
+        if self.numerator.bit_length() <= 53 && self.denominator.bit_length() <= 53 {
+            return self.numerator.to_float() / self.denominator.to_float();
+        }
+        // otherwise divide out a quotient of about 64 bits, which the float then rounds,
+        // and scale it back in two steps so that neither power of two overflows; a subnormal
+        // result only has bits down to 2^-1074, so the quotient stops one bit below that
+        // rather than being rounded once to 53 bits and again when it is scaled
+        let shift = int::min(64 + (self.denominator.bit_length() as int) - (self.numerator.bit_length() as int), 1075);
+        let (quotient, remainder) = if shift >= 0 {
+            (self.numerator * BigInt::power_of_two(shift as uint)).divide(&self.denominator)
+        } else {
+            self.numerator.divide(&(self.denominator * BigInt::power_of_two(-shift as uint)))
+        };
+        // one more bit, set when the division left a remainder, so that a quotient cut off
+        // at a halfway point still rounds up
+        let sticky = if remainder.is_zero() { 0 } else if remainder.is_negative() { -1 } else { 1 };
+        let quotient = quotient * BigInt::from_int(2) + BigInt::from_int(sticky);
+        let shift = shift + 1;
+        let half = shift / 2;
+        quotient.to_float() * float::pow(2.0, -half as float) * float::pow(2.0, (half - shift) as float)
+    }
+
+    pure fn compare(&self, other:&Fraction) -> int {
+        (self.numerator * other.denominator).compare(&(other.numerator * self.denominator))
+    }
+
+    pure fn to_str(&self) -> ~str {
+        if self.is_integer() {
+            self.numerator.to_str()
+        } else {
+            self.numerator.to_str() + ~"/" + self.denominator.to_str()
+        }
+    }
+}
+
+// the fraction with the smallest denominator from low to high inclusive, found by
+// following the continued fractions of the two ends until they differ
+pub pure fn simplest_between( low:&Fraction, high:&Fraction ) -> Fraction {
+    let zero = Fraction::from_integer(BigInt::from_int(0));
+    if low.compare(&zero) <= 0 && high.compare(&zero) >= 0 {
+        return zero;
+    }
+    if high.compare(&zero) < 0 {
+        return -simplest_between(&-*high, &-*low);
+    }
+    let whole = low.floor();
+    let whole_fraction = Fraction::from_integer(copy whole);
+    if low.compare(&whole_fraction) == 0 {
+        copy *low
+    } else if whole < high.floor() {
+        Fraction::from_integer(whole + BigInt::from_int(1))
+    } else {
+        let one = Fraction::from_integer(BigInt::from_int(1));
+        let rest = simplest_between(&(one / (*high - whole_fraction)), &(one / (*low - whole_fraction)));
+        whole_fraction + one / rest
+    }
+}
+
+pub impl Fraction : ops::Add<Fraction,Fraction> {
+    pure fn add(&self, other:&Fraction) -> Fraction {
+        Fraction::new(self.numerator * other.denominator + other.numerator * self.denominator,
+                      self.denominator * other.denominator)
+    }
+}
+
+pub impl Fraction : ops::Sub<Fraction,Fraction> {
+    pure fn sub(&self, other:&Fraction) -> Fraction {
+        self.add(&-*other)
+    }
+}
+
+pub impl Fraction : ops::Mul<Fraction,Fraction> {
+    pure fn mul(&self, other:&Fraction) -> Fraction {
+        Fraction::new(self.numerator * other.numerator, self.denominator * other.denominator)
+    }
+}
+
+pub impl Fraction : ops::Div<Fraction,Fraction> {
+    pure fn div(&self, other:&Fraction) -> Fraction {
+        Fraction::new(self.numerator * other.denominator, self.denominator * other.numerator)
+    }
+}
+
+pub impl Fraction : ops::Neg<Fraction> {
+    pure fn neg(&self) -> Fraction {
+        Fraction { numerator: -self.numerator, denominator: copy self.denominator }
+    }
+}
+
+pub impl Fraction : cmp::Eq {
+    pure fn eq(&self, other:&Fraction) -> bool {
+        self.numerator == other.numerator && self.denominator == other.denominator
+    }
+
+    pure fn ne(&self, other:&Fraction) -> bool {
+        !self.eq(other)
+    }
+}
+
+fn fraction( n:int, d:int ) -> Fraction {
+    Fraction::new(BigInt::from_int(n), BigInt::from_int(d))
+}
+
+#[test]
+fn test_that_fractions_are_kept_in_lowest_terms() {
+    assert fraction(6, 4) == fraction(3, 2);
+    assert fraction(3, -6).to_str() == ~"-1/2";
+    assert fraction(-4, -2).is_integer();
+    assert fraction(0, -5) == fraction(0, 1);
+}
+
+#[test]
+fn test_that_fraction_arithmetic_is_exact() {
+    assert fraction(1, 3) + fraction(1, 6) == fraction(1, 2);
+    assert fraction(1, 3) - fraction(1, 2) == fraction(-1, 6);
+    assert fraction(2, 3) * fraction(3, 4) == fraction(1, 2);
+    assert fraction(1, 2) / fraction(-1, 4) == fraction(-2, 1);
+}
+
+#[test]
+fn test_that_fractions_are_ordered() {
+    assert fraction(1, 3).compare(&fraction(1, 2)) < 0;
+    assert fraction(-1, 2).compare(&fraction(-2, 3)) > 0;
+    assert fraction(2, 4).compare(&fraction(1, 2)) == 0;
+}
+
+#[test]
+fn test_that_floor_rounds_down() {
+    assert fraction(7, 2).floor() == BigInt::from_int(3);
+    assert fraction(-7, 2).floor() == BigInt::from_int(-4);
+    assert fraction(-6, 2).floor() == BigInt::from_int(-3);
+}
+
+#[test]
+fn test_that_floats_convert_exactly() {
+    assert Fraction::from_float(0.5) == fraction(1, 2);
+    assert Fraction::from_float(-0.75) == fraction(-3, 4);
+    assert Fraction::from_float(0.1) == Fraction::new(BigInt::from_int(3602879701896397), BigInt::power_of_two(55));
+    for [0.1, 1.0 / 3.0, 5e-324, 1.7976931348623157e308, -2.5e-300].each |&f| {
+        assert Fraction::from_float(f).to_float() == f;
+    }
+    assert fraction(1, 3).to_float() == 1.0 / 3.0;
+}
+
+#[test]
+fn test_that_fractions_just_past_halfway_round_up() {
+    // 1 + 2^-53 + 2^-200, which truncated to 64 bits sits exactly halfway between two floats
+    let numerator = BigInt::power_of_two(200) + BigInt::power_of_two(147) + BigInt::from_int(1);
+    let above = Fraction::new(numerator, BigInt::power_of_two(200));
+    assert above.to_float() == 1.0 + float::pow(2.0, -52.0);
+    assert (-above).to_float() == -1.0 - float::pow(2.0, -52.0);
+}
+
+#[test]
+fn test_that_subnormal_fractions_round_once() {
+    // just above half the smallest subnormal, which is 2^-1074
+    let above = Fraction::new(BigInt::power_of_two(1100) + BigInt::from_int(1), BigInt::power_of_two(2175));
+    assert above.to_float() == 5e-324;
+    // exactly half rounds to even, which is zero
+    assert Fraction::new(BigInt::from_int(1), BigInt::power_of_two(1075)).to_float() == 0.0;
+    assert Fraction::new(BigInt::from_int(3), BigInt::power_of_two(1076)).to_float() == 5e-324;
+}
+
+#[test]
+fn test_that_simplest_between_finds_the_smallest_denominator() {
+    assert simplest_between(&fraction(3, 10), &fraction(4, 10)) == fraction(1, 3);
+    assert simplest_between(&fraction(-4, 10), &fraction(-3, 10)) == fraction(-1, 3);
+    assert simplest_between(&fraction(-1, 2), &fraction(1, 2)) == fraction(0, 1);
+    assert simplest_between(&fraction(5, 2), &fraction(7, 2)) == fraction(3, 1);
+    assert simplest_between(&fraction(1, 4), &fraction(1, 4)) == fraction(1, 4);
+}
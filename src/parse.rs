@@ -1,52 +1,559 @@
+use rand::RngUtil;
+
+pub enum ParseErrorKind {
+    UnexpectedCloseParen,
+    MismatchedCloseParen(~str, ~str),
+    UnterminatedList,
+    UnterminatedString,
+    UnterminatedSymbol,
+    UnterminatedBlockComment,
+    UnexpectedEndOfInput,
+    MissingDatum(~str),
+    MisplacedDot,
+    BadString(~str),
+    BadSymbol(~str),
+    BadNumber(~str),
+    BadHashSyntax(~str),
+    BadDatumLabel(~str)
+}
+
+pub struct ParseError {
+    kind: ParseErrorKind,
+    line: uint,
+    column: uint,
+    start: uint,
+    end: uint
+}
+
+pub impl ParseError {
+    pure fn message(&self) -> ~str {
+        match copy self.kind {
+            UnexpectedCloseParen => ~"Unexpected ) or ]",
+            MismatchedCloseParen(open, close) => fmt!("A list opened with %s cannot be closed with %s", open, close),
+            UnterminatedList => ~"Unterminated list (mismatched parens?)",
+            UnterminatedString => ~"Unterminated string literal",
+            UnterminatedSymbol => ~"Unterminated |symbol|",
+            UnterminatedBlockComment => ~"Unterminated block comment",
+            UnexpectedEndOfInput => ~"Unexpected end of input",
+            MissingDatum(prefix) => fmt!("Expected a datum after %s", prefix),
+            MisplacedDot => ~"A . must follow at least one datum and be followed by exactly one more before the )",
+            BadString(message) => message,
+            BadSymbol(message) => message,
+            BadNumber(message) => message,
+            BadHashSyntax(message) => message,
+            BadDatumLabel(message) => message
+        }
+    }
+
+    pure fn to_str(&self) -> ~str {
+        fmt!("line %u, column %u: %s", self.line, self.column, self.message())
+    }
+
+    // true when more input could still turn this into a valid program
+    pure fn is_incomplete(&self) -> bool {
+        match self.kind {
+            UnterminatedList | UnterminatedString | UnterminatedSymbol |
+            UnterminatedBlockComment | UnexpectedEndOfInput => true,
+            _ => false
+        }
+    }
+}
+
+pub struct Token {
+    text: ~str,
+    line: uint,
+    column: uint,
+    start: uint,
+    end: uint
+}
+
+pub impl Token {
+    // whitespace and comments, which the reader skips
+    pure fn is_trivia(&self) -> bool {
+        str::starts_with(self.text, ";") || str::starts_with(self.text, "#|") ||
+            char::is_whitespace(str::char_at(self.text, 0))
+    }
+}
+
+pub fn error_at_token( kind:ParseErrorKind, token:&Token ) -> ParseError {
+    ParseError { kind: kind, line: token.line, column: token.column, start: token.start, end: token.end }
+}
+
+// where each character starts, with one extra entry for the end of input
+struct Position {
+    line: uint,
+    column: uint,
+    offset: uint
+}
+
+fn positions( chars:&[char] ) -> ~[Position] {
+    let mut result:~[Position] = ~[];
+    let mut line = 1u;
+    let mut column = 1u;
+    let mut offset = 0u;
+    for chars.each() |&c| {
+        result.push(Position { line: line, column: column, offset: offset });
+        offset += str::from_char(c).len();
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    result.push(Position { line: line, column: column, offset: offset });
+    result
+}
+
+fn make_token( chars:&[char], positions:&[Position], start:uint, end:uint ) -> Token {
+    Token {
+        text: str::from_chars(vec::slice(chars, start, end)),
+        line: positions[start].line,
+        column: positions[start].column,
+        start: positions[start].offset,
+        end: positions[end].offset
+    }
+}
+
+fn error_at( kind:ParseErrorKind, positions:&[Position], start:uint, end:uint ) -> ParseError {
+    ParseError {
+        kind: kind,
+        line: positions[start].line,
+        column: positions[start].column,
+        start: positions[start].offset,
+        end: positions[end].offset
+    }
+}
+
+fn token_text( input:&str ) -> ~[~str] {
+    match tokenize( input ) {
+        Ok(tokens) => tokens.map(|token| copy token.text),
+        Err(error) => fail error.to_str()
+    }
+}
 
 #[test]
 fn test_tokenize() {
-    assert tokenize( ~"(1 2 3)" ) == ~[~"(", ~"1", ~"2", ~"3", ~")"];
+    assert token_text( ~"(1 2 3)" ) == ~[~"(", ~"1", ~"2", ~"3", ~")"];
 }
 
 #[test]
 fn test_tokenize_empty() {
-    assert tokenize( ~"" ) == ~[];
+    assert token_text( ~"" ) == ~[];
 }
 
 #[test]
 fn test_tokenize_no_spaces() {
-    assert tokenize( ~"one" ) == ~[~"one"];
+    assert token_text( ~"one" ) == ~[~"one"];
+}
+
+#[test]
+fn test_tokenize_without_spaces_around_parentheses() {
+    assert token_text( ~"(1 2 3)" ) == token_text( ~" ( 1 2 3 ) " );
+    assert token_text( ~"((a)b)" ) == ~[~"(", ~"(", ~"a", ~")", ~"b", ~")"];
+}
+
+#[test]
+fn test_tokenize_one_parenthesis() {
+    assert token_text( ~"(" ) == ~[~"("];
+    assert token_text( ~")" ) == ~[~")"];
+}
+
+#[test]
+fn test_tokenize_keeps_strings_together() {
+    assert token_text( ~"(display \"hello world\")" ) == ~[~"(", ~"display", ~"\"hello world\"", ~")"];
+}
+
+#[test]
+fn test_tokenize_string_with_escaped_quote_and_parens() {
+    assert token_text( ~"\"a \\\" (b)\" c" ) == ~[~"\"a \\\" (b)\"", ~"c"];
+}
+
+#[test]
+fn test_tokenize_characters() {
+    assert token_text( ~"(#\\a #\\space)" ) == ~[~"(", ~"#\\a", ~"#\\space", ~")"];
+    assert token_text( ~"(#\\( #\\) #\\ )" ) == ~[~"(", ~"#\\(", ~"#\\)", ~"#\\ ", ~")"];
+}
+
+#[test]
+fn test_tokenize_unterminated_string() {
+    match tokenize( ~"(a \"abc" ) {
+        Err(ParseError { kind: UnterminatedString, line: 1, column: 4, start: 3, end: 7 }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_tokenize_skips_line_comments() {
+    assert token_text( ~"; a header\n(1 2) ; trailing\n3" ) == ~[~"(", ~"1", ~"2", ~")", ~"3"];
+    assert token_text( ~"abc;comment" ) == ~[~"abc"];
+}
+
+#[test]
+fn test_tokenize_skips_nested_block_comments() {
+    assert token_text( ~"1 #| a #| nested |# comment |# 2" ) == ~[~"1", ~"2"];
+    assert token_text( ~"(a #|x|#b)" ) == ~[~"(", ~"a", ~"b", ~")"];
+}
+
+#[test]
+fn test_tokenize_unterminated_block_comments() {
+    match tokenize( ~"1\n  #| never closed" ) {
+        Err(ParseError { kind: UnterminatedBlockComment, line: 2, column: 3, start: 4, end: 19 }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_tokenize_datum_comments() {
+    assert token_text( ~"#;(a b) c" ) == ~[~"#;", ~"(", ~"a", ~"b", ~")", ~"c"];
+}
+
+#[test]
+fn test_tokenize_comment_characters_in_strings() {
+    assert token_text( ~"\"; #| not a comment\"" ) == ~[~"\"; #| not a comment\""];
+}
+
+#[test]
+fn test_tokenize_quote_abbreviations() {
+    assert token_text( ~"'a `(b ,c ,@d)" ) == ~[~"'", ~"a", ~"`", ~"(", ~"b", ~",", ~"c", ~",@", ~"d", ~")"];
+    assert token_text( ~"'()" ) == ~[~"'", ~"(", ~")"];
+}
+
+#[test]
+fn test_tokenize_vector_literals() {
+    assert token_text( ~"#(1 #(2))" ) == ~[~"#(", ~"1", ~"#(", ~"2", ~")", ~")"];
+}
+
+#[test]
+fn test_tokenize_bytevector_literals() {
+    assert token_text( ~"#u8(1 2)" ) == ~[~"#u8(", ~"1", ~"2", ~")"];
+    assert token_text( ~"#u8 (1)" ) == ~[~"#u8", ~"(", ~"1", ~")"];
+    assert token_text( ~"#U8(1 2)" ) == ~[~"#U8(", ~"1", ~"2", ~")"];
+}
+
+#[test]
+fn test_tokenize_square_brackets() {
+    assert token_text( ~"[let ([x 1]) x]" ) == ~[~"[", ~"let", ~"(", ~"[", ~"x", ~"1", ~"]", ~")", ~"x", ~"]"];
+}
+
+#[test]
+fn test_tokenize_quoted_symbols() {
+    assert token_text( ~"(|hello world| |a\\|b|)" ) == ~[~"(", ~"|hello world|", ~"|a\\|b|", ~")"];
+    assert token_text( ~"a|b c|" ) == ~[~"a", ~"|b c|"];
+}
+
+#[test]
+fn test_tokenize_unterminated_quoted_symbol() {
+    match tokenize( ~"(a |b c" ) {
+        Err(ParseError { kind: UnterminatedSymbol, line: 1, column: 4, _ }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_tokenize_fold_case_directives() {
+    assert token_text( ~"#!fold-case ABC #!no-fold-case" ) == ~[~"#!fold-case", ~"ABC", ~"#!no-fold-case"];
+}
+
+#[test]
+fn test_tokenize_datum_labels() {
+    assert token_text( ~"#0=(a #0#)" ) == ~[~"#0=", ~"(", ~"a", ~"#0#", ~")"];
+    assert token_text( ~"#12='x" ) == ~[~"#12=", ~"'", ~"x"];
+    assert token_text( ~"#=x" ) == ~[~"#=x"];
+}
+
+#[test]
+fn test_lex_keeps_whitespace_and_comments() {
+    let program = ~"(a  ; note\n #| block |# b)\n";
+    let tokens = lex( program ).get();
+    assert tokens.map(|token| copy token.text) ==
+        ~[~"(", ~"a", ~"  ", ~"; note", ~"\n ", ~"#| block |#", ~" ", ~"b", ~")", ~"\n"];
+    assert str::concat(tokens.map(|token| copy token.text)) == program;
+    assert tokens.filtered(|token| token.is_trivia()).len() == 5;
+}
+
+#[test]
+fn test_tokenize_tracks_positions() {
+    match tokenize( ~"(a\n  \"\u03bb\" bc)" ) {
+        Ok([open, a, lambda, bc, close]) => {
+            assert open.line == 1 && open.column == 1 && open.start == 0 && open.end == 1;
+            assert a.line == 1 && a.column == 2 && a.start == 1 && a.end == 2;
+            assert lambda.line == 2 && lambda.column == 3 && lambda.start == 5 && lambda.end == 9;
+            assert bc.line == 2 && bc.column == 7 && bc.start == 10 && bc.end == 12;
+            assert close.line == 2 && close.column == 9 && close.start == 12 && close.end == 13;
+        }
+        _ => fail
+    }
+}
+
+fn is_delimiter( c:char ) -> bool {
+    char::is_whitespace(c) || c == '(' || c == ')' || c == '[' || c == ']' ||
+        c == '"' || c == ';' || c == '|'
+}
+
+fn starts_block_comment( chars:&[char], i:uint ) -> bool {
+    i + 1 < chars.len() && chars[i] == '#' && chars[i + 1] == '|'
+}
+
+fn ends_block_comment( chars:&[char], i:uint ) -> bool {
+    i + 1 < chars.len() && chars[i] == '|' && chars[i + 1] == '#'
+}
+
+// the length of the #n= or #n# datum label starting at i, or 0 if there isn't one
+fn datum_label_length( chars:&[char], i:uint ) -> uint {
+    let mut end = i + 1;
+    while end < chars.len() && char::is_digit(chars[end]) {
+        end += 1;
+    }
+    if end > i + 1 && end < chars.len() && (chars[end] == '=' || chars[end] == '#') {
+        end + 1 - i
+    } else {
+        0
+    }
+}
+
+// every token in input, including whitespace and comments, so that the tokens
+// put back together are exactly the input
+pub fn lex( input:&str ) -> Result<~[Token], ParseError> {
+    let chars = str::chars(input);
+    let positions = positions(chars);
+    let mut tokens:~[Token] = ~[];
+    let mut i = 0u;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if char::is_whitespace(c) {
+            while i < chars.len() && char::is_whitespace(chars[i]) {
+                i += 1;
+            }
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == ';' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            tokens.push(make_token(chars, positions, start, i));
+        } else if starts_block_comment(chars, i) {
+            let mut depth = 0u;
+            loop {
+                if i >= chars.len() {
+                    return Err(error_at(UnterminatedBlockComment, positions, start, i));
+                } else if starts_block_comment(chars, i) {
+                    depth += 1;
+                    i += 2;
+                } else if ends_block_comment(chars, i) {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == '#' && i + 1 < chars.len() && chars[i + 1] == ';' {
+            i += 2;
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == '#' && i + 1 < chars.len() && chars[i + 1] == '(' {
+            i += 2;
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == '#' && i + 3 < chars.len() && (chars[i + 1] == 'u' || chars[i + 1] == 'U') && chars[i + 2] == '8' && chars[i + 3] == '(' {
+            i += 4;
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == '#' && datum_label_length(chars, i) > 0 {
+            i += datum_label_length(chars, i);
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == '(' || c == ')' || c == '[' || c == ']' || c == '\'' || c == '`' {
+            i += 1;
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == ',' {
+            if i + 1 < chars.len() && chars[i + 1] == '@' {
+                i += 2;
+            } else {
+                i += 1;
+            }
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == '#' && i + 1 < chars.len() && chars[i + 1] == '\\' {
+            // the character after #\ is always part of the token, even a delimiter
+            i = uint::min(i + 3, chars.len());
+            while i < chars.len() && !is_delimiter(chars[i]) {
+                i += 1;
+            }
+            tokens.push(make_token(chars, positions, start, i));
+        } else if c == '"' || c == '|' {
+            // strings and |symbols| keep their quotes so that atom can tell them from plain symbols
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            if i >= chars.len() {
+                let kind = if c == '"' { UnterminatedString } else { UnterminatedSymbol };
+                return Err(error_at(kind, positions, start, chars.len()));
+            }
+            i += 1;
+            tokens.push(make_token(chars, positions, start, i));
+        } else {
+            while i < chars.len() && !is_delimiter(chars[i]) {
+                i += 1;
+            }
+            tokens.push(make_token(chars, positions, start, i));
+        }
+    }
+    Ok(tokens)
+}
+
+// the tokens the reader works from, without the whitespace and comments
+fn tokenize( input:&str ) -> Result<~[Token], ParseError> {
+    match lex( input ) {
+        Ok(tokens) => Ok(tokens.filtered(|token| !token.is_trivia())),
+        Err(error) => Err(error)
+    }
+}
+
+#[test]
+fn test_that_string_literal_reads_plain_strings() {
+    assert string_literal( ~"\"hello world\"" ).get() == String(~"hello world");
+    assert string_literal( ~"\"\"" ).get() == String(~"");
+}
+
+#[test]
+fn test_that_string_literal_reads_escapes() {
+    assert string_literal( ~"\"a\\nb\"" ).get() == String(~"a\nb");
+    assert string_literal( ~"\"a\\tb\"" ).get() == String(~"a\tb");
+    assert string_literal( ~"\"a\\\\b\"" ).get() == String(~"a\\b");
+    assert string_literal( ~"\"a\\\"b\"" ).get() == String(~"a\"b");
+    assert string_literal( ~"\"\\x41;BC\"" ).get() == String(~"ABC");
 }
 
 #[test]
-fn test_pad_parentheses() {
-    assert pad_parentheses( ~"(1 2 3)" ) == ~" ( 1 2 3 ) "
+fn test_that_string_literal_joins_continued_lines() {
+    assert string_literal( ~"\"abc \\\n    def\"" ).get() == String(~"abc def");
 }
 
 #[test]
-fn test_pad_parentheses_empty() {
-    assert pad_parentheses( ~"" ) == ~""
+fn test_that_string_literal_rejects_bad_escapes() {
+    assert string_literal( ~"\"\\q\"" ).is_err();
+    assert string_literal( ~"\"\\x41\"" ).is_err();
+    assert string_literal( ~"\"\\xZZ;\"" ).is_err();
 }
 
 #[test]
-fn test_pad_parentheses_one() {
-    assert pad_parentheses( ~"(" ) == ~" ( ";
-    assert pad_parentheses( ~")" ) == ~" ) ";
+fn test_that_string_literal_rejects_unterminated_strings() {
+    assert string_literal( ~"\"abc" ).is_err();
+    assert string_literal( ~"\"abc\\\"" ).is_err();
+}
+
+fn is_intraline_whitespace( c:char ) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn string_literal( token:&str ) -> Result<Expression, ParseErrorKind> {
+    match escaped_text( token, ~"string" ) {
+        Ok(text) => Ok(String(text)),
+        Err(kind) => Err(kind)
+    }
+}
+
+fn symbol_literal( token:&str ) -> Result<Expression, ParseErrorKind> {
+    match escaped_text( token, ~"symbol" ) {
+        Ok(text) => Ok(Symbol(text)),
+        Err(kind) => Err(kind)
+    }
+}
+
+// the error for a bad "string" or |symbol|, told apart by its quote
+fn bad_escaped_text( quote:char, message:~str ) -> ParseErrorKind {
+    if quote == '"' { BadString(message) } else { BadSymbol(message) }
 }
 
-fn pad_parentheses( input:&str ) -> ~str {
-    str::replace(str::replace(input, ")", " ) "), "(", " ( ")
+// the text between the quotes of a "string" or |symbol|, with escapes replaced
+fn escaped_text( token:&str, what:&str ) -> Result<~str, ParseErrorKind> {
+    let chars = str::chars(token);
+    let quote = chars[0];
+    let mut result = ~"";
+    let mut i = 1u;
+    while i < chars.len() {
+        match chars[i] {
+            c if c == quote => {
+                return if i == chars.len() - 1 {
+                    Ok(result)
+                } else {
+                    Err(bad_escaped_text(quote, fmt!("Unexpected characters after %s %s", what, token)))
+                };
+            }
+            '\\' => {
+                i += 1;
+                if i >= chars.len() {
+                    break;
+                }
+                match chars[i] {
+                    'n' => str::push_char(&mut result, '\n'),
+                    't' => str::push_char(&mut result, '\t'),
+                    'r' => str::push_char(&mut result, '\r'),
+                    'a' => str::push_char(&mut result, '\x07'),
+                    'b' => str::push_char(&mut result, '\x08'),
+                    '\\' => str::push_char(&mut result, '\\'),
+                    '"' => str::push_char(&mut result, '"'),
+                    '|' => str::push_char(&mut result, '|'),
+                    'x' | 'X' => {
+                        let start = i + 1;
+                        while i < chars.len() && chars[i] != ';' && chars[i] != quote {
+                            i += 1;
+                        }
+                        if i >= chars.len() || chars[i] != ';' {
+                            return Err(bad_escaped_text(quote, fmt!("Hex escape in %s %s must end with a semicolon", what, token)));
+                        }
+                        let digits = str::from_chars(vec::slice(chars, start, i));
+                        match hex_scalar_value(digits) {
+                            Some(c) => str::push_char(&mut result, c),
+                            None => return Err(bad_escaped_text(quote, fmt!("Invalid hex escape \\x%s; in %s", digits, what)))
+                        }
+                    }
+                    c if is_intraline_whitespace(c) || c == '\n' => {
+                        // \<intraline whitespace><line ending><intraline whitespace> is dropped
+                        while i < chars.len() && is_intraline_whitespace(chars[i]) {
+                            i += 1;
+                        }
+                        if i >= chars.len() || chars[i] != '\n' {
+                            return Err(bad_escaped_text(quote, fmt!("Backslash followed by whitespace must end the line in %s %s", what, token)));
+                        }
+                        while i + 1 < chars.len() && is_intraline_whitespace(chars[i + 1]) {
+                            i += 1;
+                        }
+                    }
+                    c => return Err(bad_escaped_text(quote, fmt!("Unknown escape sequence \\%c in %s", c, what)))
+                }
+            }
+            c => str::push_char(&mut result, c)
+        }
+        i += 1;
+    }
+    Err(if quote == '"' { UnterminatedString } else { UnterminatedSymbol })
 }
 
-fn tokenize( input:&str ) -> ~[~str] {
-    str::words(pad_parentheses(input))
+fn hex_scalar_value( digits:&str ) -> Option<char> {
+    if digits.len() == 0 {
+        return None;
+    }
+    match uint::from_str_radix(digits, 16) {
+        Some(value) if value <= 0x10FFFF && (value < 0xD800 || value > 0xDFFF) => Some(value as char),
+        _ => None
+    }
 }
 
 #[test]
 fn test_that_atom_can_read_bools() {
-    assert atom(~"#t") == Bool(true)
+    assert atom(~"#t").get() == Bool(true);
+    assert atom(~"#false").get() == Bool(false);
 }
 
 #[test]
 fn test_that_atom_can_read_a_symbol() {
     match atom(~"hello") {
-        Symbol(~"hello") => (),
+        Ok(Symbol(~"hello")) => (),
             _ => fail
     }
 }
@@ -54,23 +561,23 @@ fn test_that_atom_can_read_a_symbol() {
 #[test]
 fn test_that_a_plus_sign_becomes_a_symbol() {
     match atom(~"+") {
-        Symbol(~"+") => (),
-        _ => fail fmt!("+ became: %s", atom(~"+").to_str())
+        Ok(Symbol(~"+")) => (),
+        _ => fail fmt!("+ became: %s", atom(~"+").get().to_str())
     }
 }
 
 #[test]
 fn test_that_a_minus_sign_becomes_a_symbol() {
     match atom(~"-") {
-        Symbol(~"-") => (),
-        _ => fail fmt!("- became: %s", atom(~"-").to_str())
+        Ok(Symbol(~"-")) => (),
+        _ => fail fmt!("- became: %s", atom(~"-").get().to_str())
     }
 }
 
 #[test]
 fn test_that_atom_can_read_an_int() {
     match atom(~"10") {
-        Int(10) => (),
+        Ok(Int(10)) => (),
             _ => fail
     }
 }
@@ -78,83 +585,1547 @@ fn test_that_atom_can_read_an_int() {
 #[test]
 fn test_that_atom_can_read_a_float() {
     match atom(~"10.1") {
-        Float(10.1) => (),
+        Ok(Float(10.1)) => (),
             _ => fail
     }
 }
 
-fn atom( input:~str ) -> Expression {
-    match input {
-        ~"#t" => Bool( true ),
-        ~"#f" => Bool( false ),
-        ~"+" => Symbol( ~"+" ),
-        ~"-" => Symbol( ~"-" ),
-        _ => match int::from_str(input) {
-            Some(number) => Int(number),
-            None => match float::from_str(input) {
-                Some(number) => Float(number),
-                None => Symbol(input)
+#[test]
+fn test_that_character_literal_reads_single_characters() {
+    assert character_literal( ~"#\\a" ).get() == Char('a');
+    assert character_literal( ~"#\\A" ).get() == Char('A');
+    assert character_literal( ~"#\\(" ).get() == Char('(');
+    assert character_literal( ~"#\\ " ).get() == Char(' ');
+    assert character_literal( ~"#\\x" ).get() == Char('x');
+}
+
+#[test]
+fn test_that_character_literal_reads_names() {
+    assert character_literal( ~"#\\space" ).get() == Char(' ');
+    assert character_literal( ~"#\\newline" ).get() == Char('\n');
+    assert character_literal( ~"#\\tab" ).get() == Char('\t');
+    assert character_literal( ~"#\\nul" ).get() == Char('\x00');
+    assert character_literal( ~"#\\delete" ).get() == Char('\x7f');
+}
+
+#[test]
+fn test_that_character_literal_reads_hex_scalar_values() {
+    assert character_literal( ~"#\\x41" ).get() == Char('A');
+    assert character_literal( ~"#\\x3bb" ).get() == Char('\u03bb');
+}
+
+#[test]
+fn test_that_character_literal_rejects_unknown_names() {
+    assert character_literal( ~"#\\monkey" ).is_err();
+    assert character_literal( ~"#\\xD800" ).is_err();
+    assert character_literal( ~"#\\" ).is_err();
+}
+
+fn character_literal( token:&str ) -> Result<Expression, ParseErrorKind> {
+    let chars = str::chars(token);
+    let name = str::from_chars(vec::slice(chars, 2, chars.len()));
+    if chars.len() == 3 {
+        return Ok(Char(chars[2]));
+    }
+    match name {
+        ~"space" => Ok(Char(' ')),
+        ~"newline" => Ok(Char('\n')),
+        ~"tab" => Ok(Char('\t')),
+        ~"return" => Ok(Char('\r')),
+        ~"nul" | ~"null" => Ok(Char('\x00')),
+        ~"alarm" => Ok(Char('\x07')),
+        ~"backspace" => Ok(Char('\x08')),
+        ~"escape" => Ok(Char('\x1b')),
+        ~"delete" => Ok(Char('\x7f')),
+        _ => {
+            if chars.len() > 3 && (chars[2] == 'x' || chars[2] == 'X') {
+                match hex_scalar_value(str::from_chars(vec::slice(chars, 3, chars.len()))) {
+                    Some(c) => Ok(Char(c)),
+                    None => Err(BadHashSyntax(fmt!("Invalid character scalar value %s", token)))
+                }
+            } else {
+                Err(BadHashSyntax(fmt!("Unknown character name %s", token)))
             }
         }
     }
 }
 
 #[test]
-fn test_that_read_can_read_an_atom() {
-    let atom = read( ~[~"12"] );
-    match atom {
-        Int(12) => (),
-            _ => fail
+fn test_that_atom_can_read_a_character() {
+    match atom(~"#\\a") {
+        Ok(Char('a')) => (),
+        _ => fail
     }
 }
 
 #[test]
-fn test_that_read_can_read_a_list() {
-    let list = read( ~[ ~"(", ~"1", ~")" ] );
-    match list {
-        List([Int(1)]) => (),
-            _ => fail ~"not a list"
+fn test_that_atom_can_read_a_string() {
+    match atom(~"\"hello\"") {
+        Ok(String(~"hello")) => (),
+        _ => fail
     }
 }
 
 #[test]
-fn test_that_read_can_read_a_nested_list() {
-    let list = read( ~[ ~"(", ~"1", ~"(", ~"2", ~")", ~"3", ~")" ] );
-    match list {
-        List([Int(1), List([Int(2)]), Int(3)]) => (),
+fn test_that_atom_can_read_a_quoted_symbol() {
+    assert atom(~"|hello world|").get() == Symbol(~"hello world");
+    assert atom(~"||").get() == Symbol(~"");
+    assert atom(~"|a\\|b\\x41;|").get() == Symbol(~"a|bA");
+    assert atom(~"|12|").get() == Symbol(~"12");
+    match atom(~"|a\\qb|") {
+        Err(BadSymbol(_)) => (),
         _ => fail
     }
 }
 
-fn read( tokens:~[~str] ) -> Expression {
-    fn subexpression( tokens:~[~str] ) -> (Expression, ~[~str]) {
-        let mut remainder = copy tokens;
-        let token = remainder.remove(0);
-        match token {
-            ~"(" => {
-                let mut accumulator:~[Expression] = ~[];
-                while remainder.len() > 0 && remainder[0] != ~")" {
-                    let (expr, new_remainder) = subexpression( remainder );
-                    accumulator.push(expr);
-                    remainder = new_remainder
-                }
-                if remainder.len() == 0 {
-                    (Error(~"Unexpected end of input (mismatched parens?)"), remainder)
-                } else {
-                    // remove the close paren
-                    remainder.remove(0);
-                    (List(accumulator), remainder)
-                }
-            }
-            ~")" => fail,
-            _ => (atom(token), remainder)
+#[test]
+fn test_that_atom_rejects_unknown_hash_syntax() {
+    match atom(~"#monkey") {
+        Err(BadHashSyntax(_)) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_number_reads_integers_in_every_radix() {
+    assert number( ~"42" ).get().get() == Int(42);
+    assert number( ~"-42" ).get().get() == Int(-42);
+    assert number( ~"+42" ).get().get() == Int(42);
+    assert number( ~"#xFF" ).get().get() == Int(255);
+    assert number( ~"#xff" ).get().get() == Int(255);
+    assert number( ~"#X-1f" ).get().get() == Int(-31);
+    assert number( ~"#b1010" ).get().get() == Int(10);
+    assert number( ~"#o17" ).get().get() == Int(15);
+    assert number( ~"#d99" ).get().get() == Int(99);
+    assert number( fmt!("%d", int::max_value) ).get().get() == Int(int::max_value);
+    assert number( fmt!("%d", int::min_value) ).get().get() == Int(int::min_value);
+    assert number( fmt!("%d", int::max_value) + ~"0" ).get().is_err();
+}
+
+#[test]
+fn test_that_number_reads_decimals_and_exponents() {
+    assert number( ~"1.5" ).get().get() == Float(1.5);
+    assert number( ~".5" ).get().get() == Float(0.5);
+    assert number( ~"-.5" ).get().get() == Float(-0.5);
+    assert number( ~"5." ).get().get() == Float(5.0);
+    assert number( ~"1e10" ).get().get() == Float(1e10);
+    assert number( ~"1.5E-3" ).get().get() == Float(1.5e-3);
+    assert number( ~"-2e+2" ).get().get() == Float(-200.0);
+}
+
+#[test]
+fn test_that_number_reads_infinities_and_nans() {
+    assert number( ~"+inf.0" ).get().get() == Float(float::infinity);
+    assert number( ~"-inf.0" ).get().get() == Float(float::neg_infinity);
+    match number( ~"-nan.0" ) {
+        Some(Ok(Float(x))) => assert float::is_NaN(x),
+        _ => fail
+    }
+    match number( ~"+nan.0" ) {
+        Some(Ok(Float(x))) => assert float::is_NaN(x),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_number_applies_exactness_prefixes() {
+    assert number( ~"#i3" ).get().get() == Float(3.0);
+    assert number( ~"#e2.0" ).get().get() == Int(2);
+    assert number( ~"#e1e3" ).get().get() == Int(1000);
+    assert number( ~"#x#e10" ).get().get() == Int(16);
+    assert number( ~"#e#x10" ).get().get() == Int(16);
+    assert number( ~"#i#b11" ).get().get() == Float(3.0);
+}
+
+#[test]
+fn test_that_number_reads_ratios() {
+    assert number( ~"1/3" ).get().get() == Int(1) / Int(3);
+    assert number( ~"-4/6" ).get().get().to_str() == ~"-2/3";
+    assert number( ~"#e1.5" ).get().get().to_str() == ~"3/2";
+    assert number( ~"#e0.1" ).get().get().to_str() == ~"1/10";
+    assert number( ~"#e-1.25e-1" ).get().get().to_str() == ~"-1/8";
+    assert number( ~"6/3" ).get().get() == Int(2);
+    assert number( ~"-6/3" ).get().get() == Int(-2);
+    assert number( ~"#x10/2" ).get().get() == Int(8);
+    assert number( ~"#i1/4" ).get().get() == Float(0.25);
+}
+
+#[test]
+fn test_that_number_reads_complex_numbers() {
+    assert number( ~"3+4i" ).get().get() == Complex(3.0, 4.0);
+    assert number( ~"1.5-2i" ).get().get() == Complex(1.5, -2.0);
+    assert number( ~"+i" ).get().get() == Complex(0.0, 1.0);
+    assert number( ~"-2.5i" ).get().get() == Complex(0.0, -2.5);
+    assert number( ~"1/2+1/4i" ).get().get() == Complex(0.5, 0.25);
+    assert number( ~"1e-3+1e+3i" ).get().get() == Complex(0.001, 1000.0);
+    assert number( ~"#x10-ai" ).get().get() == Complex(16.0, -10.0);
+    assert number( ~"+inf.0i" ).get().get() == Complex(0.0, float::infinity);
+    assert number( ~"3+0i" ).get().get() == Int(3);
+    assert number( ~"2@0" ).get().get() == Int(2);
+    assert number( ~"1@3.141592653589793" ).get().get().to_str() == ~"-1.0+1.2246467991473532e-16i";
+}
+
+#[test]
+fn test_that_number_reads_integers_too_large_for_an_int() {
+    assert number( ~"99999999999999999999999" ).get().get().to_str() == ~"99999999999999999999999";
+    assert number( ~"-9223372036854775808" ).get().get() == Int(int::min_value);
+    assert number( ~"9223372036854775808" ).get().get().to_str() == ~"9223372036854775808";
+    assert number( ~"#x10000000000000000" ).get().get().to_str() == ~"18446744073709551616";
+    assert number( ~"#e1e20" ).get().get().to_str() == ~"100000000000000000000";
+    assert number( ~"200000000000000000000/2" ).get().get().to_str() == ~"100000000000000000000";
+}
+
+#[test]
+fn test_that_number_leaves_symbols_alone() {
+    for [~"+", ~"-", ~"...", ~"-a", ~"+.a", ~"a1", ~"->x", ~"/2", ~"inf.0", ~"nan.0", ~"#t", ~"i", ~"-in"].each |&token| {
+        assert number( token ).is_none();
+    }
+}
+
+#[test]
+fn test_that_number_rejects_malformed_numerals() {
+    for [~"1.2.3", ~"1e", ~"1e+", ~"12abc", ~"#xZZ", ~"#b102", ~"#x1.5", ~"#e+inf.0",
+         ~"#x#x1", ~"#e#i1", ~"#x#q1", ~"1/0", ~"1/", ~"1/2/3", ~"1.5/2", ~"#e", ~"#x",
+         ~"2i", ~"1+2", ~"1+i2", ~"#e1+2i", ~"1@"].each |&token| {
+        match number( token ) {
+            Some(Err(BadNumber(_))) => (),
+            _ => fail fmt!("%s should have been a malformed number", token)
+        }
+    }
+}
+
+#[test]
+fn test_that_atom_reports_malformed_numbers() {
+    match atom(~"1.2.3") {
+        Err(BadNumber(_)) => (),
+        _ => fail
+    }
+}
+
+// a numeral as written, before any exactness prefix is applied
+enum Numeral {
+    Integer(BigInt),
+    Ratio(BigInt, BigInt),
+    // the value and the numeral it was read from, for reading it exactly
+    Decimal(float, ~[char])
+}
+
+fn ascii_lowercase( c:char ) -> char {
+    if c >= 'A' && c <= 'Z' { ((c as uint) + 32) as char } else { c }
+}
+
+// true for tokens that R7RS reserves for numbers even without a # prefix
+fn looks_numeric( body:&[char] ) -> bool {
+    fn digit_at( body:&[char], i:uint ) -> bool {
+        i < body.len() && char::is_digit(body[i])
+    }
+
+    fn unsigned_start( body:&[char], i:uint ) -> bool {
+        digit_at(body, i) || (i < body.len() && body[i] == '.' && digit_at(body, i + 1))
+    }
+
+    if unsigned_start(body, 0) {
+        true
+    } else if body.len() > 0 && (body[0] == '+' || body[0] == '-') {
+        let rest = str::from_chars(vec::slice(body, 1, body.len()));
+        unsigned_start(body, 1) || rest == ~"inf.0" || rest == ~"nan.0" ||
+            rest == ~"i" || rest == ~"inf.0i" || rest == ~"nan.0i"
+    } else {
+        false
+    }
+}
+
+fn signed_integer( digits:&[char], radix:uint, negative:bool ) -> Result<BigInt, ~str> {
+    if digits.len() == 0 {
+        return Err(~"missing digits");
+    }
+    let multiplier = BigInt::from_int(radix as int);
+    let mut value = BigInt::from_int(0);
+    for digits.each() |&c| {
+        match char::to_digit(c, radix) {
+            Some(digit) => value = value * multiplier + BigInt::from_int(digit as int),
+            None => return Err(fmt!("%c is not a base %u digit", c, radix))
         }
     }
+    Ok(if negative { -value } else { value })
+}
 
-    let (expression, _remainder) = subexpression( tokens );
-    expression
+// digits, an optional fraction and an optional exponent, with at least one digit before the exponent
+fn decimal( body:&[char] ) -> Result<float, ~str> {
+    let mut i = 0u;
+    let mut mantissa_digits = 0u;
+    while i < body.len() && char::is_digit(body[i]) {
+        i += 1;
+        mantissa_digits += 1;
+    }
+    if i < body.len() && body[i] == '.' {
+        i += 1;
+        while i < body.len() && char::is_digit(body[i]) {
+            i += 1;
+            mantissa_digits += 1;
+        }
+    }
+    if mantissa_digits == 0 {
+        return Err(~"missing digits");
+    }
+    let mantissa_end = i;
+    if i < body.len() && (body[i] == 'e' || body[i] == 'E') {
+        i += 1;
+        if i < body.len() && (body[i] == '+' || body[i] == '-') {
+            i += 1;
+        }
+        let exponent_start = i;
+        while i < body.len() && char::is_digit(body[i]) {
+            i += 1;
+        }
+        if i == exponent_start {
+            return Err(~"missing exponent digits");
+        }
+    }
+    if i != body.len() {
+        return Err(fmt!("unexpected %c", body[i]));
+    }
+    // float::from_str wants digits on both sides of the point
+    let mut text = ~"0" + str::from_chars(vec::slice(body, 0, mantissa_end));
+    if mantissa_end > 0 && body[mantissa_end - 1] == '.' {
+        text += ~"0";
+    }
+    text += str::from_chars(vec::slice(body, mantissa_end, body.len()));
+    match float::from_str(text) {
+        Some(value) => Ok(value),
+        None => Err(~"unreadable decimal")
+    }
 }
 
-pub fn parse( program:&str ) -> Expression {
-    read( tokenize( program ) )
+fn numeral( body:&[char], radix:uint ) -> Result<Numeral, ~str> {
+    let negative = body.len() > 0 && body[0] == '-';
+    let signed = body.len() > 0 && (body[0] == '+' || body[0] == '-');
+    let unsigned = vec::slice(body, if signed { 1 } else { 0 }, body.len());
+    let text = str::from_chars(unsigned);
+
+    if signed && text == ~"inf.0" {
+        return Ok(Decimal(if negative { float::neg_infinity } else { float::infinity }, vec::from_slice(body)));
+    }
+    if signed && text == ~"nan.0" {
+        return Ok(Decimal(float::NaN, vec::from_slice(body)));
+    }
+    match vec::position_elem(unsigned, &'/') {
+        Some(slash) => {
+            let numerator = signed_integer(vec::slice(unsigned, 0, slash), radix, negative);
+            let denominator = signed_integer(vec::slice(unsigned, slash + 1, unsigned.len()), radix, false);
+            return match (numerator, denominator) {
+                (Ok(n), Ok(d)) => if d.is_zero() { Err(~"division by zero") } else { Ok(Ratio(n, d)) },
+                (Err(message), _) | (_, Err(message)) => Err(message)
+            };
+        }
+        None => ()
+    }
+    match signed_integer(unsigned, radix, negative) {
+        Ok(n) => Ok(Integer(n)),
+        Err(message) => {
+            if radix != 10 || vec::all(unsigned, |&c| char::is_digit(c)) {
+                Err(message)
+            } else {
+                match decimal(unsigned) {
+                    Ok(value) => Ok(Decimal(if negative { -value } else { value }, vec::from_slice(body))),
+                    Err(message) => Err(message)
+                }
+            }
+        }
+    }
+}
+
+// the exact value of a numeral that decimal has accepted, so that #e0.1 is 1/10 rather
+// than the float nearest to it
+fn exact_decimal( body:&[char] ) -> Result<Fraction, ~str> {
+    let ten = BigInt::from_int(10);
+    let mut digits = BigInt::from_int(0);
+    let mut scale = 0;
+    let mut after_point = false;
+    let mut i = 0u;
+    while i < body.len() && body[i] != 'e' && body[i] != 'E' {
+        match body[i] {
+            '.' => after_point = true,
+            '+' | '-' => (),
+            c => {
+                digits = digits * ten + BigInt::from_int(char::to_digit(c, 10).get() as int);
+                if after_point {
+                    scale -= 1;
+                }
+            }
+        }
+        i += 1;
+    }
+    if i < body.len() {
+        let mut exponent = vec::slice(body, i + 1, body.len());
+        if exponent[0] == '+' {
+            exponent = vec::slice(exponent, 1, exponent.len());
+        }
+        match int::from_str(str::from_chars(exponent)) {
+            Some(value) if value > -10000 && value < 10000 => scale += value,
+            _ => return Err(~"exponent is too large for an exact number")
+        }
+    }
+    let mut power = BigInt::from_int(1);
+    for uint::range(0, int::abs(scale) as uint) |_| {
+        power = power * ten;
+    }
+    if body[0] == '-' {
+        digits = -digits;
+    }
+    if scale >= 0 {
+        Ok(Fraction::from_integer(digits * power))
+    } else {
+        Ok(Fraction::new(digits, power))
+    }
+}
+
+fn apply_exactness( numeral:Numeral, exact:Option<bool> ) -> Result<Expression, ~str> {
+    match (numeral, exact) {
+        (Integer(n), Some(false)) => Ok(Float(n.to_float())),
+        (Integer(n), _) => Ok(Expression::from_bigint(n)),
+        (Ratio(n, d), Some(false)) => Ok(Float(Fraction::new(n, d).to_float())),
+        (Ratio(n, d), _) => Ok(Expression::from_fraction(Fraction::new(n, d))),
+        (Decimal(value, body), Some(true)) => {
+            if float::is_NaN(value) || float::is_infinite(value) {
+                Err(~"infinities and NaN have no exact value")
+            } else {
+                match exact_decimal(body) {
+                    Ok(fraction) => Ok(Expression::from_fraction(fraction)),
+                    Err(message) => Err(message)
+                }
+            }
+        }
+        (Decimal(value, _), _) => Ok(Float(value))
+    }
+}
+
+fn real( body:&[char], radix:uint, exact:Option<bool> ) -> Result<Expression, ~str> {
+    match numeral(body, radix) {
+        Ok(numeral) => apply_exactness(numeral, exact),
+        Err(message) => Err(message)
+    }
+}
+
+// a real number, or a complex one written as a+bi, +bi or magnitude@angle
+fn complex( body:&[char], radix:uint, exact:Option<bool> ) -> Result<Expression, ~str> {
+    let result = match vec::position_elem(body, &'@') {
+        Some(at) => {
+            match (real(vec::slice(body, 0, at), radix, exact), real(vec::slice(body, at + 1, body.len()), radix, exact)) {
+                (Ok(magnitude), Ok(angle)) => Expression::new_polar(&magnitude, &angle),
+                (Err(message), _) | (_, Err(message)) => return Err(message)
+            }
+        }
+        None => {
+            if body.len() == 0 || body[body.len() - 1] != 'i' {
+                return real(body, radix, exact);
+            }
+            // the imaginary part starts at the last sign that is not in an exponent
+            let end = body.len() - 1;
+            let mut start = 0u;
+            for uint::range(1, end) |i| {
+                let in_exponent = radix == 10 && (body[i - 1] == 'e' || body[i - 1] == 'E');
+                if (body[i] == '+' || body[i] == '-') && !in_exponent {
+                    start = i;
+                }
+            }
+            if body[start] != '+' && body[start] != '-' {
+                return Err(~"the imaginary part needs a sign");
+            }
+            let real_part = if start == 0 { Ok(Int(0)) } else { real(vec::slice(body, 0, start), radix, exact) };
+            let imaginary_part = match vec::slice(body, start, end) {
+                ['+'] => Ok(Int(1)),
+                ['-'] => Ok(Int(-1)),
+                digits => real(digits, radix, exact)
+            };
+            match (real_part, imaginary_part) {
+                (Ok(x), Ok(y)) => Expression::new_rectangular(&x, &y),
+                (Err(message), _) | (_, Err(message)) => return Err(message)
+            }
+        }
+    };
+    match (result, exact) {
+        (Complex(_, _), Some(true)) => Err(~"complex numbers are never exact"),
+        (result, _) => Ok(result)
+    }
+}
+
+// None when the token is not a number at all and should be read as something else
+fn number( token:&str ) -> Option<Result<Expression, ParseErrorKind>> {
+    let chars = str::chars(token);
+    let mut radix:Option<uint> = None;
+    let mut exact:Option<bool> = None;
+    let mut i = 0u;
+    while i < chars.len() && chars[i] == '#' {
+        if i + 1 >= chars.len() {
+            return Some(Err(BadNumber(fmt!("Malformed number prefix in %s", token))));
+        }
+        let prefix = ascii_lowercase(chars[i + 1]);
+        match prefix {
+            'x' | 'b' | 'o' | 'd' if radix.is_none() => {
+                radix = Some(match prefix { 'x' => 16, 'b' => 2, 'o' => 8, _ => 10 });
+            }
+            'e' | 'i' if exact.is_none() => exact = Some(prefix == 'e'),
+            'x' | 'b' | 'o' | 'd' | 'e' | 'i' => {
+                return Some(Err(BadNumber(fmt!("Repeated number prefix in %s", token))));
+            }
+            _ => {
+                if i == 0 {
+                    return None;
+                }
+                return Some(Err(BadNumber(fmt!("Unknown number prefix in %s", token))));
+            }
+        }
+        i += 2;
+    }
+    let body = vec::slice(chars, i, chars.len());
+    if i == 0 && !looks_numeric(body) {
+        return None;
+    }
+    Some(match complex(body, radix.get_default(10), exact) {
+        Ok(expression) => Ok(expression),
+        Err(message) => Err(BadNumber(fmt!("Malformed number %s: %s", token, message)))
+    })
+}
+
+fn atom( input:~str ) -> Result<Expression, ParseErrorKind> {
+    if str::starts_with(input, "\"") {
+        return string_literal(input);
+    }
+    if str::starts_with(input, "|") {
+        return symbol_literal(input);
+    }
+    if str::starts_with(input, "#\\") {
+        return character_literal(input);
+    }
+    match input {
+        ~"#t" | ~"#true" => Ok(Bool( true )),
+        ~"#f" | ~"#false" => Ok(Bool( false )),
+        ~"+" => Ok(Symbol( ~"+" )),
+        ~"-" => Ok(Symbol( ~"-" )),
+        _ => match number(input) {
+            Some(result) => result,
+            None => {
+                if str::starts_with(input, "#") {
+                    Err(BadHashSyntax(fmt!("Unknown syntax %s", input)))
+                } else {
+                    Ok(Symbol(input))
+                }
+            }
+        }
+    }
+}
+
+// the token that closes a list opened with open
+pub fn closer( open:&Token ) -> ~str {
+    if open.text == ~"[" { ~"]" } else { ~")" }
+}
+
+// what #!fold-case does to a token; strings, |symbols| and single characters keep their case
+fn fold_case( text:~str ) -> ~str {
+    if str::starts_with(text, "\"") || str::starts_with(text, "|") ||
+        (str::starts_with(text, "#\\") && str::char_len(text) == 3) {
+        return text;
+    }
+    str::from_chars(str::chars(text).map(|&c| ascii_lowercase(c)))
+}
+
+// the number in a #n= definition or #n# reference, and whether it is a definition
+fn datum_label( text:&str ) -> Option<(uint, bool)> {
+    let chars = str::chars(text);
+    if datum_label_length(chars, 0) != chars.len() {
+        return None;
+    }
+    let digits = str::from_chars(vec::slice(chars, 1, chars.len() - 1));
+    match uint::from_str(digits) {
+        Some(label) => Some((label, chars.last() == '=')),
+        None => None
+    }
+}
+
+struct Parser {
+    tokens: ~[Token],
+    mut position: uint,
+    // set and cleared by the #!fold-case and #!no-fold-case directives
+    mut fold_case: bool,
+    // each datum label defined so far in the current top-level datum, and what it
+    // stands for once that datum has been read
+    mut labels: ~[(uint, Option<Expression>)]
+}
+
+impl Parser {
+    fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    fn next_is(&self, text:~str) -> bool {
+        !self.at_end() && self.tokens[self.position].text == text
+    }
+
+    fn next_is_close(&self) -> bool {
+        self.next_is(~")") || self.next_is(~"]")
+    }
+
+    // consumes the ) or ] that is next, as long as it is the right one for open
+    fn close(&self, open:&Token) -> Result<(), ParseError> {
+        let close = self.next();
+        if close.text == closer(open) {
+            Ok(())
+        } else {
+            Err(error_at_token(MismatchedCloseParen(copy open.text, copy close.text), &close))
+        }
+    }
+
+    fn next(&self) -> Token {
+        let token = copy self.tokens[self.position];
+        self.position += 1;
+        token
+    }
+
+    // drops each #; along with the datum that follows it, and obeys any #! directives
+    fn skip_datum_comments(&self) -> Result<(), ParseError> {
+        loop {
+            if self.next_is(~"#;") {
+                let comment = self.next();
+                match self.required_datum(&comment) {
+                    Ok(_) => (),
+                    Err(error) => return Err(error)
+                }
+            } else if self.next_is(~"#!fold-case") {
+                self.position += 1;
+                self.fold_case = true;
+            } else if self.next_is(~"#!no-fold-case") {
+                self.position += 1;
+                self.fold_case = false;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    // the datum that must follow a prefix such as ' or #;
+    fn required_datum(&self, prefix:&Token) -> Result<Expression, ParseError> {
+        match self.skip_datum_comments() {
+            Ok(()) => (),
+            Err(error) => return Err(error)
+        }
+        if self.at_end() {
+            Err(error_at_token(UnexpectedEndOfInput, prefix))
+        } else if self.next_is_close() {
+            Err(error_at_token(MissingDatum(copy prefix.text), prefix))
+        } else {
+            self.datum()
+        }
+    }
+
+    // assumes datum comments have been skipped and there is at least one token left
+    fn datum(&self) -> Result<Expression, ParseError> {
+        let token = self.next();
+        match datum_label(token.text) {
+            Some((label, true)) => return self.labelled_datum(&token, label),
+            Some((label, false)) => return self.label_reference(&token, label),
+            None => ()
+        }
+        match copy token.text {
+            ~"(" | ~"[" => self.list(&token, @mut Cons { car: Nil, cdr: Nil }),
+            ~"#(" => self.vector(&token, @mut ~[]),
+            ~"#u8(" | ~"#U8(" => self.bytevector(&token),
+            ~")" | ~"]" => Err(error_at_token(UnexpectedCloseParen, &token)),
+            ~"." => Err(error_at_token(MisplacedDot, &token)),
+            ~"'" => self.abbreviation(&token, ~"quote"),
+            ~"`" => self.abbreviation(&token, ~"quasiquote"),
+            ~"," => self.abbreviation(&token, ~"unquote"),
+            ~",@" => self.abbreviation(&token, ~"unquote-splicing"),
+            text => match atom(if self.fold_case { fold_case(text) } else { text }) {
+                Ok(expression) => Ok(expression),
+                Err(kind) => Err(error_at_token(kind, &token))
+            }
+        }
+    }
+
+    // fills in head, the first pair of the list, which a datum label may already refer to;
+    // an empty list leaves it unused
+    fn list(&self, open:&Token, head:@mut Cons) -> Result<Expression, ParseError> {
+        let mut accumulator:~[Expression] = ~[];
+        loop {
+            match self.skip_datum_comments() {
+                Ok(()) => (),
+                Err(error) => return Err(error)
+            }
+            if self.at_end() {
+                return Err(error_at_token(UnterminatedList, open));
+            }
+            if self.next_is_close() {
+                return match self.close(open) {
+                    Ok(()) => Ok(link(accumulator, Nil, head)),
+                    Err(error) => Err(error)
+                };
+            }
+            if self.next_is(~".") && accumulator.len() > 0 {
+                let dot = self.next();
+                return self.dotted_tail(&dot, open, accumulator, head);
+            }
+            match self.datum() {
+                Ok(expression) => accumulator.push(expression),
+                Err(error) => return Err(error)
+            }
+        }
+    }
+
+    // fills in cell, which a datum label may already refer to
+    fn vector(&self, open:&Token, cell:@mut ~[Expression]) -> Result<Expression, ParseError> {
+        let mut items:~[Expression] = ~[];
+        loop {
+            match self.skip_datum_comments() {
+                Ok(()) => (),
+                Err(error) => return Err(error)
+            }
+            if self.at_end() {
+                return Err(error_at_token(UnterminatedList, open));
+            }
+            if self.next_is_close() {
+                return match self.close(open) {
+                    Ok(()) => {
+                        *cell = items;
+                        Ok(Vector(cell))
+                    }
+                    Err(error) => Err(error)
+                };
+            }
+            match self.datum() {
+                Ok(expression) => items.push(expression),
+                Err(error) => return Err(error)
+            }
+        }
+    }
+
+    fn bytevector(&self, open:&Token) -> Result<Expression, ParseError> {
+        let mut bytes:~[u8] = ~[];
+        loop {
+            match self.skip_datum_comments() {
+                Ok(()) => (),
+                Err(error) => return Err(error)
+            }
+            if self.at_end() {
+                return Err(error_at_token(UnterminatedList, open));
+            }
+            if self.next_is_close() {
+                return match self.close(open) {
+                    Ok(()) => Ok(Bytevector(@mut bytes)),
+                    Err(error) => Err(error)
+                };
+            }
+            let element = copy self.tokens[self.position];
+            match self.datum() {
+                Ok(Int(n)) if n >= 0 && n <= 255 => bytes.push(n as u8),
+                Ok(other) => return Err(error_at_token(BadHashSyntax(fmt!("Bytevector elements must be integers from 0 to 255, not %s", other.to_str())), &element)),
+                Err(error) => return Err(error)
+            }
+        }
+    }
+
+    // reads the datum after the . and the ) that must follow it
+    fn dotted_tail(&self, dot:&Token, open:&Token, heads:~[Expression], head:@mut Cons) -> Result<Expression, ParseError> {
+        let tail = match self.required_datum(dot) {
+            Ok(expression) => expression,
+            Err(error) => return Err(error)
+        };
+        match self.skip_datum_comments() {
+            Ok(()) => (),
+            Err(error) => return Err(error)
+        }
+        if self.at_end() {
+            Err(error_at_token(UnterminatedList, open))
+        } else if self.next_is_close() {
+            match self.close(open) {
+                Ok(()) => Ok(link(heads, tail, head)),
+                Err(error) => Err(error)
+            }
+        } else {
+            Err(error_at_token(MisplacedDot, dot))
+        }
+    }
+
+    fn label_definition(&self, label:uint) -> Option<Option<Expression>> {
+        for self.labels.each() |entry| {
+            match copy *entry {
+                (defined, expression) if defined == label => return Some(expression),
+                _ => ()
+            }
+        }
+        None
+    }
+
+    // a list or vector is registered before its items are read so that they can refer back
+    // to it; anything else only gets its label once it has been read, since it cannot
+    // contain itself
+    fn labelled_datum(&self, definition:&Token, label:uint) -> Result<Expression, ParseError> {
+        if self.label_definition(label).is_some() {
+            return Err(error_at_token(BadDatumLabel(fmt!("Datum label #%u= is defined twice", label)), definition));
+        }
+        match self.skip_datum_comments() {
+            Ok(()) => (),
+            Err(error) => return Err(error)
+        }
+        if self.next_is(~"#(") {
+            let open = self.next();
+            let cell = @mut ~[];
+            self.labels.push((label, Some(Vector(cell))));
+            return self.vector(&open, cell);
+        }
+        if self.next_is(~"(") || self.next_is(~"[") {
+            let open = self.next();
+            let head = @mut Cons { car: Nil, cdr: Nil };
+            self.labels.push((label, Some(Pair(head))));
+            let index = self.labels.len() - 1;
+            let result = self.list(&open, head);
+            match copy result {
+                // the empty list has no pair to stand for it
+                Ok(expression) => self.labels[index] = (label, Some(expression)),
+                Err(_) => ()
+            }
+            return result;
+        }
+        self.labels.push((label, None));
+        let index = self.labels.len() - 1;
+        match self.required_datum(definition) {
+            Ok(expression) => {
+                self.labels[index] = (label, Some(copy expression));
+                Ok(expression)
+            }
+            Err(error) => Err(error)
+        }
+    }
+
+    fn label_reference(&self, reference:&Token, label:uint) -> Result<Expression, ParseError> {
+        match self.label_definition(label) {
+            Some(Some(expression)) => Ok(expression),
+            Some(None) => Err(error_at_token(BadDatumLabel(fmt!("#%u# refers to the datum that contains it, but only lists and vectors can contain themselves", label)), reference)),
+            None => Err(error_at_token(BadDatumLabel(fmt!("Datum label #%u# is used before it is defined", label)), reference))
+        }
+    }
+
+    fn abbreviation(&self, prefix:&Token, name:~str) -> Result<Expression, ParseError> {
+        match self.required_datum(prefix) {
+            Ok(expression) => Ok(Expression::new_list(~[Symbol(name), expression])),
+            Err(error) => Err(error)
+        }
+    }
+}
+
+// heads chained onto tail, starting from the pair head
+fn link( heads:~[Expression], tail:Expression, head:@mut Cons ) -> Expression {
+    if heads.len() == 0 {
+        return tail;
+    }
+    head.car = copy heads[0];
+    head.cdr = Expression::new_dotted(vec::tail(heads), tail);
+    Pair(head)
+}
+
+// reads every datum from tokens that have had their whitespace and comments removed
+pub fn read_all( tokens:~[Token] ) -> Result<~[Expression], ParseError> {
+    let parser = Parser { tokens: tokens, position: 0, fold_case: false, labels: ~[] };
+    let mut expressions:~[Expression] = ~[];
+    loop {
+        match parser.skip_datum_comments() {
+            Ok(()) => (),
+            Err(error) => return Err(error)
+        }
+        if parser.at_end() {
+            return Ok(expressions);
+        }
+        // datum labels only reach as far as the top-level datum they are defined in
+        parser.labels = ~[];
+        match parser.datum() {
+            Ok(expression) => expressions.push(expression),
+            Err(error) => return Err(error)
+        }
+    }
+}
+
+fn parse_ok( program:&str ) -> Expression {
+    match parse( program ) {
+        Ok(expression) => expression,
+        Err(error) => fail error.to_str()
+    }
+}
+
+#[test]
+fn test_that_read_can_read_an_atom() {
+    match parse_ok( ~"12" ) {
+        Int(12) => (),
+            _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_can_read_a_list() {
+    match parse_ok( ~"(1)" ).list_items() {
+        Some([Int(1)]) => (),
+            _ => fail ~"not a list"
+    }
+}
+
+#[test]
+fn test_that_read_can_read_a_nested_list() {
+    assert parse_ok( ~"(1 (2) 3)" ) == Expression::new_list(~[Int(1), Expression::new_list(~[Int(2)]), Int(3)]);
+}
+
+#[test]
+fn test_that_read_skips_datum_comments() {
+    match parse_ok( ~"(1 #;(2) 3)" ).list_items() {
+        Some([Int(1), Int(3)]) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_skips_datum_comments_at_the_end_of_a_list() {
+    match parse_ok( ~"(1 #;2)" ).list_items() {
+        Some([Int(1)]) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_skips_leading_datum_comments() {
+    match parse_ok( ~"#; #; 1 2 3" ) {
+        Int(3) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_expands_quote_abbreviations() {
+    assert parse_ok( ~"'a" ) == parse_ok( ~"(quote a)" );
+    assert parse_ok( ~"`(a ,b ,@c)" ) == parse_ok( ~"(quasiquote (a (unquote b) (unquote-splicing c)))" );
+    assert parse_ok( ~"''a" ) == parse_ok( ~"(quote (quote a))" );
+    assert parse_ok( ~"'()" ) == parse_ok( ~"(quote ())" );
+}
+
+#[test]
+fn test_that_read_requires_a_datum_after_a_quote() {
+    match parse( ~"(a ')" ) {
+        Err(ParseError { kind: MissingDatum(~"'"), line: 1, column: 4, start: 3, end: 4 }) => (),
+        _ => fail
+    }
+    match parse( ~"(a #;)" ) {
+        Err(ParseError { kind: MissingDatum(~"#;"), _ }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_can_read_dotted_pairs() {
+    match parse_ok( ~"(a . b)" ).list_parts() {
+        ([Symbol(~"a")], Symbol(~"b")) => (),
+        _ => fail
+    }
+    match parse_ok( ~"(a b . c)" ).list_parts() {
+        ([Symbol(~"a"), Symbol(~"b")], Symbol(~"c")) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_folds_dotted_lists_into_proper_lists() {
+    assert parse_ok( ~"(a . (b c))" ) == parse_ok( ~"(a b c)" );
+    assert parse_ok( ~"(a . (b . c))" ) == parse_ok( ~"(a b . c)" );
+    assert parse_ok( ~"(a . ())" ) == parse_ok( ~"(a)" );
+}
+
+#[test]
+fn test_that_read_still_reads_dots_inside_symbols_and_numbers() {
+    assert parse_ok( ~"(... .5 a.b)" ) == Expression::new_list(~[Symbol(~"..."), Float(0.5), Symbol(~"a.b")]);
+}
+
+#[test]
+fn test_that_read_rejects_misplaced_dots() {
+    for [~"(. a)", ~"(a . b c)", ~"(a .)", ~".", ~"(a . . b)"].each |&program| {
+        match parse( program ) {
+            Err(ParseError { kind: MisplacedDot, _ }) => (),
+            Err(ParseError { kind: MissingDatum(~"."), _ }) => (),
+            _ => fail fmt!("%s should not have parsed", program)
+        }
+    }
+}
+
+#[test]
+fn test_that_read_can_read_vectors() {
+    assert parse_ok( ~"#(1 #(2) (3))" ) == Vector(@mut ~[Int(1), Vector(@mut ~[Int(2)]), Expression::new_list(~[Int(3)])]);
+    assert parse_ok( ~"#()" ) == Vector(@mut ~[]);
+}
+
+#[test]
+fn test_that_read_rejects_dots_in_vectors() {
+    match parse( ~"#(1 . 2)" ) {
+        Err(ParseError { kind: MisplacedDot, _ }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_can_read_bytevectors() {
+    assert parse_ok( ~"#u8(0 255 16)" ) == Bytevector(@mut ~[0u8, 255u8, 16u8]);
+    assert parse_ok( ~"#u8()" ) == Bytevector(@mut ~[]);
+    assert parse_ok( ~"#u8(#xff)" ) == Bytevector(@mut ~[255u8]);
+    assert parse_ok( ~"#U8(1 2)" ) == Bytevector(@mut ~[1u8, 2u8]);
+}
+
+#[test]
+fn test_that_read_rejects_bytevector_elements_that_are_not_bytes() {
+    for [~"#u8(256)", ~"#u8(-1)", ~"#u8(a)", ~"#u8(1.0)", ~"#u8((1))"].each |&program| {
+        match parse( program ) {
+            Err(ParseError { kind: BadHashSyntax(_), line: 1, column: 5, _ }) => (),
+            _ => fail fmt!("%s should not have parsed", program)
+        }
+    }
+}
+
+#[test]
+fn test_that_read_treats_square_brackets_as_parentheses() {
+    assert parse_ok( ~"[let ([x 1]) x]" ) == parse_ok( ~"(let ((x 1)) x)" );
+    assert parse_ok( ~"[a . b]" ) == parse_ok( ~"(a . b)" );
+}
+
+#[test]
+fn test_that_read_rejects_mismatched_brackets() {
+    match parse( ~"(a b]" ) {
+        Err(ParseError { kind: MismatchedCloseParen(~"(", ~"]"), line: 1, column: 5, _ }) => (),
+        _ => fail
+    }
+    match parse( ~"[a . b)" ) {
+        Err(ParseError { kind: MismatchedCloseParen(~"[", ~")"), _ }) => (),
+        _ => fail
+    }
+    match parse( ~"#(1 2]" ) {
+        Err(ParseError { kind: MismatchedCloseParen(~"#(", ~"]"), _ }) => (),
+        _ => fail
+    }
+    match parse( ~"]" ) {
+        Err(ParseError { kind: UnexpectedCloseParen, _ }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_can_read_quoted_symbols() {
+    assert parse_ok( ~"(|hello world| x)" ) == Expression::new_list(~[Symbol(~"hello world"), Symbol(~"x")]);
+    assert parse_ok( ~"'|a b|" ) == Expression::new_list(~[Symbol(~"quote"), Symbol(~"a b")]);
+}
+
+#[test]
+fn test_that_read_folds_case_after_the_fold_case_directive() {
+    assert parse_all( ~"Abc #!fold-case Abc #T #\\SPACE #\\A \"Abc\" |Abc| #!no-fold-case Abc" ).get() ==
+        ~[Symbol(~"Abc"), Symbol(~"abc"), Bool(true), Char(' '), Char('A'),
+          String(~"Abc"), Symbol(~"Abc"), Symbol(~"Abc")];
+    assert parse_ok( ~"(#!fold-case FOO)" ) == Expression::new_list(~[Symbol(~"foo")]);
+}
+
+#[test]
+fn test_that_written_symbols_read_back() {
+    for [~"", ~"hello world", ~"1", ~"+5", ~".", ~"a|b", ~"a\\b", ~"#x", ~"x;y", ~"..."].each |&name| {
+        assert parse_ok( Symbol(copy name).to_str() ) == Symbol(copy name);
+    }
+}
+
+#[test]
+fn test_that_read_can_read_datum_labels() {
+    assert parse_ok( ~"(#0=(a b) #0# c)" ) == parse_ok( ~"((a b) (a b) c)" );
+    assert parse_ok( ~"#1=x" ) == Symbol(~"x");
+}
+
+#[test]
+fn test_that_read_shares_labelled_vectors() {
+    match parse_ok( ~"(#0=#(1) #0#)" ).list_items() {
+        Some([Vector(x), Vector(y)]) => assert managed::mut_ptr_eq(x, y),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_can_read_cyclic_vectors() {
+    match parse_ok( ~"#0=#(1 #0#)" ) {
+        Vector(outer) => match copy (*outer)[1] {
+            Vector(inner) => assert managed::mut_ptr_eq(outer, inner),
+            _ => fail
+        },
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_shares_labelled_lists() {
+    match parse_ok( ~"(#0=(1) . #0#)" ) {
+        Pair(outer) => match (copy outer.car, copy outer.cdr) {
+            (Pair(x), Pair(y)) => assert managed::mut_ptr_eq(x, y),
+            _ => fail
+        },
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_read_can_read_cyclic_lists() {
+    match parse_ok( ~"#0=(a b . #0#)" ) {
+        Pair(head) => match copy head.cdr {
+            Pair(second) => match copy second.cdr {
+                Pair(third) => assert managed::mut_ptr_eq(head, third),
+                _ => fail
+            },
+            _ => fail
+        },
+        _ => fail
+    }
+    assert parse_ok( ~"#0=(a #0#)" ).to_str() == ~"#0=(a #0#)";
+}
+
+#[test]
+fn test_that_written_shared_structure_reads_back() {
+    let cyclic = parse_ok( ~"#0=#(a #1=#(b #0#) #1#)" );
+    assert cyclic.to_str() == ~"#0=#(a #1=#(b #0#) #1#)";
+    assert parse_ok( cyclic.to_str() ).to_str() == cyclic.to_str();
+}
+
+#[test]
+fn test_that_read_rejects_bad_datum_labels() {
+    for [~"#0#", ~"(#0=a #0=b)", ~"#0='(a #0#)", ~"(#0=a) #0#"].each |&program| {
+        match parse_all( program ) {
+            Err(ParseError { kind: BadDatumLabel(_), _ }) => (),
+            _ => fail fmt!("%s should not have parsed", program)
+        }
+    }
+}
+
+#[test]
+fn test_that_read_can_read_a_list_of_strings() {
+    match parse_ok( ~"(\"a b\" \"c\")" ).list_items() {
+        Some([String(~"a b"), String(~"c")]) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_parse_ignores_documented_source() {
+    let program = ~";;; square.scm -- squares things
+#| Usage:
+   #| (square 2) |# => 4
+|#
+(define square ; the procedure
+  (lambda (x) #;(display x) (* x x)))";
+    assert parse_ok( program ) == parse_ok( ~"(define square (lambda (x) (* x x)))" );
+}
+
+#[test]
+fn test_that_parse_reports_a_stray_close_paren() {
+    match parse( ~"(a b))" ) {
+        Err(ParseError { kind: UnexpectedCloseParen, line: 1, column: 6, start: 5, end: 6 }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_parse_reports_where_an_unterminated_list_started() {
+    match parse( ~"(a\n (b c)\n (d" ) {
+        Err(ParseError { kind: UnterminatedList, line: 3, column: 2, start: 11, end: 12 }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_parse_reports_bad_hash_syntax() {
+    match parse( ~"(list #\\monkey)" ) {
+        Err(ParseError { kind: BadHashSyntax(_), line: 1, column: 7, start: 6, end: 14 }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_parse_reports_bad_string_escapes() {
+    match parse( ~"\"a\\qb\"" ) {
+        Err(ParseError { kind: BadString(_), line: 1, column: 1, _ }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_parse_reports_bad_symbol_escapes_as_symbol_errors() {
+    match parse( ~"(a |b\\x41|)" ) {
+        Err(ParseError { kind: BadSymbol(_), line: 1, column: 4, _ }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_parse_of_nothing_is_an_unexpected_end_of_input() {
+    match parse( ~" ; nothing\n" ) {
+        Err(ParseError { kind: UnexpectedEndOfInput, line: 2, column: 1, start: 11, end: 11 }) => (),
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_parse_errors_know_when_more_input_would_help() {
+    assert parse( ~"(a" ).get_err().is_incomplete();
+    assert parse( ~"\"abc" ).get_err().is_incomplete();
+    assert parse( ~"#| abc" ).get_err().is_incomplete();
+    assert parse( ~"'" ).get_err().is_incomplete();
+    assert !parse( ~")" ).get_err().is_incomplete();
+    assert !parse( ~"#monkey" ).get_err().is_incomplete();
+}
+
+pub fn parse( program:&str ) -> Result<Expression, ParseError> {
+    match parse_all( program ) {
+        Ok(expressions) => {
+            if expressions.len() > 0 {
+                Ok(copy expressions[0])
+            } else {
+                let chars = str::chars(program);
+                Err(error_at(UnexpectedEndOfInput, positions(chars), chars.len(), chars.len()))
+            }
+        }
+        Err(error) => Err(error)
+    }
+}
+
+#[test]
+fn test_that_parse_all_returns_every_top_level_datum_in_order() {
+    match parse_all( ~"(define x 1) (+ x 1)\n; done\n" ) {
+        Ok([first, second]) => {
+            assert first == Expression::new_list(~[Symbol(~"define"), Symbol(~"x"), Int(1)]);
+            assert second == Expression::new_list(~[Symbol(~"+"), Symbol(~"x"), Int(1)]);
+        }
+        _ => fail
+    }
+}
+
+#[test]
+fn test_that_parse_all_of_an_empty_program_is_empty() {
+    assert parse_all( ~"" ).get().len() == 0;
+    assert parse_all( ~"  ; nothing here" ).get().len() == 0;
+    assert parse_all( ~"#;1" ).get().len() == 0;
+}
+
+pub fn parse_all( program:&str ) -> Result<~[Expression], ParseError> {
+    match tokenize( program ) {
+        Ok(tokens) => read_all( tokens ),
+        Err(error) => Err(error)
+    }
+}
+
+pub enum ReadStatus {
+    Datum(Expression),
+    // also returned once a closed reader has nothing left
+    NeedMoreInput,
+    SyntaxError(ParseError)
+}
+
+// reads data one at a time from text that arrives in pieces
+pub struct IncrementalReader {
+    mut pending: ~str,
+    mut closed: bool,
+    // #!fold-case stays in effect for the rest of the input, not just the datum it came before
+    mut fold_case: bool,
+    // lists still open in a datum that had a syntax error, whose rest is skipped as it arrives
+    mut unclosed: uint
+}
+
+pub impl IncrementalReader {
+    static fn new() -> IncrementalReader {
+        IncrementalReader { pending: ~"", closed: false, fold_case: false, unclosed: 0 }
+    }
+
+    fn feed(&self, chunk:&str) {
+        self.pending += chunk;
+    }
+
+    // no more input is coming, so trailing atoms are complete and anything unfinished is an error
+    fn close(&self) {
+        self.closed = true;
+    }
+
+    // true when part of a datum has been fed but not yet read
+    fn in_progress(&self) -> bool {
+        if self.unclosed > 0 {
+            return true;
+        }
+        match tokenize(self.pending) {
+            Ok(tokens) => tokens.len() > 0,
+            Err(error) => error.is_incomplete()
+        }
+    }
+
+    fn next(&self) -> ReadStatus {
+        let tokens = match tokenize(self.pending) {
+            Ok(tokens) => tokens,
+            Err(error) => return self.failed(~[], error)
+        };
+        if self.unclosed > 0 {
+            self.skip(tokens, self.unclosed, 0);
+            return if self.unclosed > 0 { NeedMoreInput } else { self.next() };
+        }
+        let parser = Parser { tokens: tokens, position: 0, fold_case: self.fold_case, labels: ~[] };
+        match parser.skip_datum_comments() {
+            Ok(()) => (),
+            Err(error) => return self.failed(parser.tokens, error)
+        }
+        if parser.at_end() {
+            self.pending = ~"";
+            self.fold_case = parser.fold_case;
+            return NeedMoreInput;
+        }
+        match parser.datum() {
+            Ok(expression) => {
+                let last = copy parser.tokens[parser.position - 1];
+                let delimited = last.text == ~")" || last.text == ~"]" ||
+                    str::starts_with(last.text, "\"") || str::starts_with(last.text, "|");
+                if last.end == self.pending.len() && !delimited && !self.closed {
+                    // the next chunk might continue this atom
+                    return NeedMoreInput;
+                }
+                self.pending = str::slice(self.pending, last.end, self.pending.len());
+                self.fold_case = parser.fold_case;
+                Datum(expression)
+            }
+            Err(error) => self.failed(parser.tokens, error)
+        }
+    }
+
+    // tokens are those of the pending input, which starts with the datum that failed
+    fn failed(&self, tokens:&[Token], error:ParseError) -> ReadStatus {
+        if error.is_incomplete() {
+            if !self.closed {
+                return NeedMoreInput;
+            }
+            self.pending = ~"";
+        } else {
+            self.skip(tokens, 0, error.end);
+        }
+        SyntaxError(error)
+    }
+
+    // drops tokens up to the bad one and then on to where the lists open around it close,
+    // so that no part of a bad datum is read as data of its own; when the input runs out
+    // first, the lists left open are skipped in the input that follows
+    fn skip(&self, tokens:&[Token], open:uint, bad_end:uint) {
+        let mut depth = open;
+        for tokens.each() |token| {
+            match copy token.text {
+                ~"(" | ~"[" | ~"#(" | ~"#u8(" | ~"#U8(" => depth += 1,
+                ~")" | ~"]" => if depth > 0 { depth -= 1; },
+                _ => ()
+            }
+            if depth == 0 && token.end >= bad_end {
+                self.pending = str::slice(self.pending, token.end, self.pending.len());
+                self.unclosed = 0;
+                return;
+            }
+        }
+        self.pending = ~"";
+        self.unclosed = depth;
+    }
+}
+
+#[test]
+fn test_that_the_incremental_reader_waits_for_lists_to_close() {
+    let reader = IncrementalReader::new();
+    reader.feed(~"(define x\n");
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    assert reader.in_progress();
+    reader.feed(~"  10)\n");
+    match reader.next() {
+        Datum(datum) => assert datum == Expression::new_list(~[Symbol(~"define"), Symbol(~"x"), Int(10)]),
+        _ => fail
+    }
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    assert !reader.in_progress();
+}
+
+#[test]
+fn test_that_the_incremental_reader_returns_each_datum_in_turn() {
+    let reader = IncrementalReader::new();
+    reader.feed(~"1 (a) \"b\" ; comment\n");
+    match reader.next() { Datum(Int(1)) => (), _ => fail }
+    match reader.next() { Datum(datum) => assert datum == Expression::new_list(~[Symbol(~"a")]), _ => fail }
+    match reader.next() { Datum(String(~"b")) => (), _ => fail }
+    match reader.next() { NeedMoreInput => (), _ => fail }
+}
+
+#[test]
+fn test_that_the_incremental_reader_waits_for_strings_and_comments_to_close() {
+    let reader = IncrementalReader::new();
+    reader.feed(~"\"abc\n");
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    reader.feed(~"def\"\n");
+    match reader.next() { Datum(String(~"abc\ndef")) => (), _ => fail }
+    reader.feed(~"#| a\n");
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    assert reader.in_progress();
+    reader.feed(~"b |# 2\n");
+    match reader.next() { Datum(Int(2)) => (), _ => fail }
+}
+
+#[test]
+fn test_that_the_incremental_reader_waits_for_atoms_that_may_continue() {
+    let reader = IncrementalReader::new();
+    reader.feed(~"12");
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    reader.feed(~"3 ");
+    match reader.next() { Datum(Int(123)) => (), _ => fail }
+    reader.feed(~"45");
+    reader.close();
+    match reader.next() { Datum(Int(45)) => (), _ => fail }
+}
+
+#[test]
+fn test_that_the_incremental_reader_keeps_folding_case_across_data() {
+    let reader = IncrementalReader::new();
+    reader.feed(~"#!fold-case\n");
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    reader.feed(~"Foo ");
+    match reader.next() { Datum(Symbol(~"foo")) => (), _ => fail }
+    reader.feed(~"[Bar] ");
+    match reader.next() { Datum(datum) => assert datum == Expression::new_list(~[Symbol(~"bar")]), _ => fail }
+}
+
+#[test]
+fn test_that_the_incremental_reader_reports_syntax_errors_and_recovers() {
+    let reader = IncrementalReader::new();
+    reader.feed(~") 1\n");
+    match reader.next() { SyntaxError(ParseError { kind: UnexpectedCloseParen, _ }) => (), _ => fail }
+    match reader.next() { Datum(Int(1)) => (), _ => fail }
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    reader.feed(~"2\n");
+    match reader.next() { Datum(Int(2)) => (), _ => fail }
+}
+
+#[test]
+fn test_that_the_incremental_reader_keeps_the_data_after_a_syntax_error() {
+    let reader = IncrementalReader::new();
+    reader.feed(~") (+ 1 2)");
+    match reader.next() { SyntaxError(ParseError { kind: UnexpectedCloseParen, _ }) => (), _ => fail }
+    match reader.next() {
+        Datum(datum) => assert datum == Expression::new_list(~[Symbol(~"+"), Int(1), Int(2)]),
+        _ => fail
+    }
+    match reader.next() { NeedMoreInput => (), _ => fail }
+}
+
+#[test]
+fn test_that_the_incremental_reader_skips_the_rest_of_a_bad_datum() {
+    let reader = IncrementalReader::new();
+    reader.feed(~"(define x #monkey (launch)) (+ 1 2)\n");
+    match reader.next() { SyntaxError(_) => (), _ => fail }
+    match reader.next() {
+        Datum(datum) => assert datum == Expression::new_list(~[Symbol(~"+"), Int(1), Int(2)]),
+        _ => fail
+    }
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    reader.feed(~"(define y (#monkey\n");
+    match reader.next() { SyntaxError(_) => (), _ => fail }
+    assert reader.in_progress();
+    reader.feed(~"  (launch))\n");
+    match reader.next() { NeedMoreInput => (), _ => fail }
+    assert reader.in_progress();
+    reader.feed(~") 3\n");
+    match reader.next() { Datum(Int(3)) => (), _ => fail }
+    assert !reader.in_progress();
+}
+
+#[test]
+fn test_that_a_closed_incremental_reader_reports_unfinished_input() {
+    let reader = IncrementalReader::new();
+    reader.feed(~"(a b");
+    reader.close();
+    match reader.next() { SyntaxError(ParseError { kind: UnterminatedList, _ }) => (), _ => fail }
+}
+
+// characters that each need some care from the printer somewhere
+fn awkward_characters() -> ~str {
+    ~"aZ09 +-.#|\\\"'`,;()[]\n\t\x01\x7f\u03bb"
+}
+
+fn random_text( rng:@rand::Rng ) -> ~str {
+    let mut text = ~"";
+    for uint::range(0, rng.gen_uint_range(0, 6)) |_| {
+        str::push_char(&mut text, rng.gen_char_from(awkward_characters()));
+    }
+    text
+}
+
+fn random_float( rng:@rand::Rng ) -> float {
+    // every bit pattern is a float, which covers subnormals and huge exponents;
+    // NaN is left out because it is not equal to itself
+    loop {
+        let number = unsafe { cast::transmute::<u64, f64>(rng.gen_u64()) } as float;
+        if !float::is_NaN(number) {
+            return number;
+        }
+    }
+}
+
+fn random_data( rng:@rand::Rng, depth:uint ) -> ~[Expression] {
+    vec::from_fn(rng.gen_uint_range(0, 4), |_| random_datum(rng, depth))
+}
+
+fn random_datum( rng:@rand::Rng, depth:uint ) -> Expression {
+    let kinds = if depth == 0 { 8 } else { 11 };
+    match rng.gen_uint_range(0, kinds) {
+        0 => Bool(rng.gen_bool()),
+        1 => Int(rng.gen_int()),
+        2 if rng.gen_bool() => Int(rng.gen_int()) / Int(rng.gen_int_range(1, 1000)),
+        2 => Int(rng.gen_int_range(-1000, 1000)),
+        3 if rng.gen_bool() => Int(rng.gen_int()) * Int(rng.gen_int()),
+        3 if rng.gen_bool() => Expression::new_complex(random_float(rng), random_float(rng)),
+        3 => Float(random_float(rng)),
+        4 => Symbol(random_text(rng)),
+        5 => String(random_text(rng)),
+        6 => Char(rng.gen_char_from(awkward_characters())),
+        7 => Bytevector(@mut rng.gen_bytes(rng.gen_uint_range(0, 4))),
+        8 => Expression::new_list(random_data(rng, depth - 1)),
+        9 => Vector(@mut random_data(rng, depth - 1)),
+        _ => Expression::new_dotted(random_data(rng, depth - 1), random_datum(rng, depth - 1))
+    }
+}
+
+#[test]
+fn test_that_every_written_datum_reads_back_as_an_equal_datum() {
+    let rng = rand::seeded_rng(&~[4u8, 2u8]);
+    for uint::range(0, 2000) |_| {
+        let datum = random_datum(rng, 3);
+        let written = datum.to_str();
+        match parse_all( written ) {
+            Ok([read]) => if read != datum {
+                fail fmt!("%s read back as %s", written, read.to_str())
+            },
+            Ok(_) => fail fmt!("%s read back as more than one datum", written),
+            Err(error) => fail fmt!("%s did not read back: %s", written, error.to_str())
+        }
+    }
+}
+
+#[test]
+fn test_that_every_float_reads_back_as_the_same_float() {
+    let rng = rand::seeded_rng(&~[7u8]);
+    for uint::range(0, 10000) |_| {
+        let number = random_float(rng);
+        match parse( Float(number).to_str() ) {
+            Ok(Float(read)) => assert read == number,
+            _ => fail fmt!("%s did not read back as a float", Float(number).to_str())
+        }
+    }
+}
+
+#[test]
+fn test_that_written_floats_are_no_longer_than_they_need_to_be() {
+    let rng = rand::seeded_rng(&~[9u8]);
+    for uint::range(0, 1000) |_| {
+        // a float made from a short decimal should print as that decimal
+        let mantissa = rng.gen_int_range(1, 100000);
+        let text = fmt!("%d.%d", mantissa / 100, mantissa % 100);
+        let number = parse_ok( text );
+        assert parse_ok( number.to_str() ) == number;
+        assert number.to_str().len() <= text.len() + 1;
+    }
 }
@@ -0,0 +1,232 @@
+/*
+ * Lays out data and code over several lines so that it fits in a given width
+ *
+ * Anything that fits on the rest of its line is written as it is. Otherwise
+ * a list is broken up according to what it starts with:
+ *
+ *  (define (f x)        forms with a body keep their first few arguments on
+ *    body)              the first line and indent the rest by two
+ *
+ *  (if test             if keeps its test on the first line and lines the
+ *      consequent       branches up under it, however far over that is
+ *      alternative)
+ *
+ *  (f a                 other calls line their arguments up under the first
+ *     b)                one, unless a long name would push them too far over
+ *
+ *  ((a b)               lists that do not start with a symbol are lined up
+ *   (c d))              one element per line
+ */
+
+pub const default_width:uint = 80;
+
+pub enum Indentation {
+    // how many arguments stay on the first line before the body
+    Body(uint),
+    // the test on the first line, with the branches under it
+    Conditional,
+    Aligned
+}
+
+// named is whether the form's first argument is a symbol, as in a named let
+pub fn indentation( name:&str, named:bool ) -> Indentation {
+    match name {
+        "define" | "lambda" | "let*" | "letrec" | "letrec*" | "let-values" | "let*-values" |
+        "when" | "unless" | "case" | "parameterize" | "guard" | "define-syntax" |
+        "let-syntax" | "letrec-syntax" | "syntax-rules" => Body(1),
+        // a named let has its name and bindings on the first line
+        "let" => if named { Body(2) } else { Body(1) },
+        "do" | "define-record-type" => Body(2),
+        "begin" => Body(0),
+        "if" => Conditional,
+        _ => Aligned
+    }
+}
+
+fn spaces( count:uint ) -> ~str {
+    str::repeat(" ", count)
+}
+
+// each expression on its own line, all starting at column; trailing is how many
+// closing parentheses will follow the last one
+fn stack( expressions:&[Expression], column:uint, trailing:uint, width:uint ) -> ~str {
+    let mut lines:~[~str] = ~[];
+    for expressions.eachi() |i, expression| {
+        let after = if i + 1 == expressions.len() { trailing } else { 0 };
+        lines.push(layout(expression, column, after, width));
+    }
+    str::connect(lines, ~"\n" + spaces(column))
+}
+
+// as many words on each line as fit, with every line starting at column
+fn fill( words:&[~str], column:uint, trailing:uint, width:uint ) -> ~str {
+    let mut text = ~"";
+    let mut line_length = 0u;
+    for words.eachi() |i, word| {
+        let after = if i + 1 == words.len() { trailing } else { 0 };
+        if line_length == 0 {
+            text += *word;
+            line_length = str::char_len(*word);
+        } else if column + line_length + 1 + str::char_len(*word) + after <= width {
+            text += ~" " + *word;
+            line_length += 1 + str::char_len(*word);
+        } else {
+            text += ~"\n" + spaces(column) + *word;
+            line_length = str::char_len(*word);
+        }
+    }
+    text
+}
+
+fn layout_list( items:&[Expression], column:uint, trailing:uint, width:uint ) -> ~str {
+    match copy items[0] {
+        Symbol(name) => {
+            let head = items[0].to_str();
+            let after_head = column + 1 + str::char_len(head) + 1;
+            let named = match items {
+                [_, Symbol(_), .._] => true,
+                _ => false
+            };
+            match indentation(name, named) {
+                Body(count) => {
+                    let split = uint::min(1 + count, items.len());
+                    let has_body = split < items.len();
+                    let mut text = ~"(" + head;
+                    if split > 1 {
+                        let arguments = vec::slice(items, 1, split);
+                        let after = if has_body { 0 } else { trailing + 1 };
+                        let flat = str::connect(arguments.map(|argument| argument.to_str()), ~" ");
+                        text += ~" " + if after_head + str::char_len(flat) + after <= width {
+                            flat
+                        } else {
+                            stack(arguments, after_head, after, width)
+                        };
+                    }
+                    if has_body {
+                        text += ~"\n" + spaces(column + 2) +
+                            stack(vec::slice(items, split, items.len()), column + 2, trailing + 1, width);
+                    }
+                    text + ~")"
+                }
+                Conditional => {
+                    if items.len() == 1 {
+                        ~"(" + head + ~")"
+                    } else {
+                        ~"(" + head + ~" " + stack(vec::slice(items, 1, items.len()), after_head, trailing + 1, width) + ~")"
+                    }
+                }
+                Aligned => {
+                    if items.len() == 1 {
+                        ~"(" + head + ~")"
+                    } else if after_head <= width / 2 {
+                        ~"(" + head + ~" " + stack(vec::slice(items, 1, items.len()), after_head, trailing + 1, width) + ~")"
+                    } else {
+                        // a long name would push its arguments too far over
+                        ~"(" + stack(items, column + 1, trailing + 1, width) + ~")"
+                    }
+                }
+            }
+        }
+        _ => ~"(" + stack(items, column + 1, trailing + 1, width) + ~")"
+    }
+}
+
+// the text of expression when it starts at column and is followed by trailing closing
+// parentheses, with later lines indented to match
+fn layout( expression:&Expression, column:uint, trailing:uint, width:uint ) -> ~str {
+    let flat = expression.to_str();
+    if column + str::char_len(flat) + trailing <= width {
+        return flat;
+    }
+    match copy *expression {
+        Pair(_) => match expression.list_parts() {
+            (items, Nil) => layout_list(items, column, trailing, width),
+            (items, tail) => {
+                ~"(" + stack(items, column + 1, 0, width) + ~"\n" + spaces(column + 1) +
+                    ~". " + layout(&tail, column + 3, trailing + 1, width) + ~")"
+            }
+        },
+        Vector(items) => ~"#(" + stack(copy *items, column + 2, trailing + 1, width) + ~")",
+        Bytevector(bytes) => {
+            let words = (copy *bytes).map(|&byte| fmt!("%u", byte as uint));
+            ~"#u8(" + fill(words, column + 4, trailing + 1, width) + ~")"
+        }
+        _ => flat
+    }
+}
+
+// the write form of expression, broken over lines to fit width where it can be
+pub fn pretty( expression:&Expression, width:uint ) -> ~str {
+    if expression.has_shared_structure() {
+        // the labels would have to be shared between the pieces, so leave it on one line
+        return expression.to_str();
+    }
+    layout(expression, 0, 0, width)
+}
+
+#[test]
+fn test_that_short_data_stays_on_one_line() {
+    assert pretty( &test_parse( ~"(a (b c) #(1 2))" ), 80 ) == ~"(a (b c) #(1 2))";
+}
+
+#[test]
+fn test_that_calls_line_their_arguments_up() {
+    assert pretty( &test_parse( ~"(foo (bar 1 2) (baz 3 4))" ), 16 ) ==
+        ~"(foo (bar 1 2)\n     (baz 3 4))";
+}
+
+#[test]
+fn test_that_if_lines_its_branches_up_under_the_test() {
+    assert pretty( &test_parse( ~"(if (null? xs) (quote ()) (car xs))" ), 20 ) ==
+        ~"(if (null? xs)\n    (quote ())\n    (car xs))";
+}
+
+#[test]
+fn test_that_if_keeps_its_test_on_the_first_line_when_far_over() {
+    assert pretty( &test_parse( ~"(foo-long-name (if (null? xs) (quote ()) (car xs)))" ), 30 ) ==
+        ~"(foo-long-name (if (null? xs)\n" + spaces(19) + ~"(quote ())\n" + spaces(19) + ~"(car xs)))";
+}
+
+#[test]
+fn test_that_define_and_lambda_indent_their_bodies() {
+    let program = ~"(define square (lambda (x) (* x x)))";
+    assert pretty( &test_parse( program ), 30 ) ==
+        ~"(define square\n  (lambda (x) (* x x)))";
+    assert pretty( &test_parse( program ), 20 ) ==
+        ~"(define square\n  (lambda (x)\n    (* x x)))";
+}
+
+#[test]
+fn test_that_let_keeps_its_bindings_on_the_first_line() {
+    assert pretty( &test_parse( ~"(let ((x 1) (y 2)) (+ x y))" ), 20 ) ==
+        ~"(let ((x 1) (y 2))\n  (+ x y))";
+    assert pretty( &test_parse( ~"(let loop ((i 0)) (loop (+ i 1)))" ), 20 ) ==
+        ~"(let loop ((i 0))\n  (loop (+ i 1)))";
+}
+
+#[test]
+fn test_that_data_lists_put_one_element_on_each_line() {
+    assert pretty( &test_parse( ~"((a b) (c d) . e)" ), 10 ) == ~"((a b)\n (c d)\n . e)";
+    assert pretty( &test_parse( ~"#(alpha beta gamma)" ), 10 ) == ~"#(alpha\n  beta\n  gamma)";
+}
+
+#[test]
+fn test_that_bytevectors_fill_each_line() {
+    assert pretty( &test_parse( ~"#u8(1 2 3 4 5 6)" ), 12 ) == ~"#u8(1 2 3 4\n    5 6)";
+}
+
+#[test]
+fn test_that_every_line_fits_when_it_can() {
+    let program = ~"(define (riff-shuffle deck) (begin (define take (lambda (n seq) (if (<= n 0) (quote ()) (cons (car seq) (take (- n 1) (cdr seq)))))) ((combine append) (take (mid deck) deck) (drop (mid deck) deck))))";
+    let text = pretty( &test_parse( program ), 40 );
+    for str::each_line(text) |line| {
+        assert str::char_len(line) <= 40;
+    }
+    assert test_parse( text ) == test_parse( program );
+}
+
+#[test]
+fn test_that_shared_structure_is_left_on_one_line() {
+    let cyclic = test_parse( ~"#0=#(a b c d e f g #0#)" );
+    assert pretty( &cyclic, 10 ) == cyclic.to_str();
+}
@@ -0,0 +1,144 @@
+/*
+ * Record types, made by define-record-type
+ *
+ * A record holds one value for each field of its type, and is only ever the
+ * same as itself. The constructor, predicate, accessors and modifiers that
+ * define-record-type binds are RecordProcedures, which carry the type they
+ * work on and which of these operations they do.
+ */
+
+pub struct RecordType {
+    name: ~str,
+    fields: ~[~str]
+}
+
+pub enum RecordOperation {
+    // the fields that the constructor's arguments go into, in argument order
+    Constructor(~[uint]),
+    Predicate,
+    Accessor(uint),
+    Modifier(uint)
+}
+
+pub impl RecordType {
+    // the name without the angle brackets it is usually written with, as in <point>
+    pure fn display_name(&self) -> ~str {
+        if self.name.len() > 2 && str::starts_with(self.name, "<") && str::ends_with(self.name, ">") {
+            str::slice(self.name, 1, self.name.len() - 1)
+        } else {
+            copy self.name
+        }
+    }
+}
+
+pub impl RecordOperation : cmp::Eq {
+    pure fn eq(&self, other:&RecordOperation) -> bool {
+        match (copy *self, copy *other) {
+            (Constructor(x), Constructor(y)) => x == y,
+            (Predicate, Predicate) => true,
+            (Accessor(x), Accessor(y)) => x == y,
+            (Modifier(x), Modifier(y)) => x == y,
+            _ => false
+        }
+    }
+
+    pure fn ne(&self, other:&RecordOperation) -> bool {
+        !self.eq(other)
+    }
+}
+
+// the values of a record of the given type, or an error naming the procedure
+fn fields_of( record_type:@RecordType, name:&str, arg:&Expression ) -> Result<@mut ~[Expression], Expression> {
+    match copy *arg {
+        Record(other_type, values) if managed::ptr_eq(record_type, other_type) => Ok(values),
+        _ => Err(new_error( TypeCondition, fmt!("%s requires a %s record", name, record_type.display_name()), ~[copy *arg] ))
+    }
+}
+
+// calls one of the procedures define-record-type makes; name is the procedure's, for errors
+pub fn apply_record_procedure( record_type:@RecordType, operation:&RecordOperation, name:&str, args:~[Expression] ) -> Expression {
+    let arity = match *operation {
+        Constructor(ref indices) => indices.len(),
+        Predicate | Accessor(_) => 1,
+        Modifier(_) => 2
+    };
+    if args.len() != arity {
+        return new_error( ArityCondition, fmt!("%s expects %u argument%s, got %u", name, arity, if arity == 1 { ~"" } else { ~"s" }, args.len()), copy args );
+    }
+    match copy *operation {
+        Constructor(indices) => {
+            // fields the constructor does not set start out unspecified
+            let mut values = vec::from_elem(record_type.fields.len(), Unspecified);
+            for indices.eachi() |i, &index| {
+                values[index] = copy args[i];
+            }
+            Record(record_type, @mut values)
+        }
+        Predicate => match fields_of(record_type, name, &args[0]) {
+            Ok(_) => Bool(true),
+            Err(_) => Bool(false)
+        },
+        // fields can hold errors, but one in place of the record is passed on
+        Accessor(_) | Modifier(_) if args[0].is_error() => copy args[0],
+        Accessor(index) => match fields_of(record_type, name, &args[0]) {
+            Ok(values) => copy (*values)[index],
+            Err(error) => error
+        },
+        Modifier(index) => match fields_of(record_type, name, &args[0]) {
+            Ok(values) => {
+                (*values)[index] = copy args[1];
+                Unspecified
+            }
+            Err(error) => error
+        }
+    }
+}
+
+fn point_type() -> @RecordType {
+    @RecordType { name: ~"<point>", fields: ~[~"x", ~"y"] }
+}
+
+#[test]
+fn test_that_records_are_built_and_read_by_field() {
+    let point = point_type();
+    let p = apply_record_procedure(point, &Constructor(~[1, 0]), ~"make-point", ~[Int(2), Int(1)]);
+    assert apply_record_procedure(point, &Accessor(0), ~"point-x", ~[copy p]) == Int(1);
+    assert apply_record_procedure(point, &Accessor(1), ~"point-y", ~[copy p]) == Int(2);
+    apply_record_procedure(point, &Modifier(0), ~"set-point-x!", ~[copy p, Int(5)]);
+    assert apply_record_procedure(point, &Accessor(0), ~"point-x", ~[p]) == Int(5);
+}
+
+#[test]
+fn test_that_predicates_only_accept_records_of_their_own_type() {
+    let point = point_type();
+    let other = point_type();
+    let p = apply_record_procedure(point, &Constructor(~[0, 1]), ~"make-point", ~[Int(1), Int(2)]);
+    assert apply_record_procedure(point, &Predicate, ~"point?", ~[copy p]) == Bool(true);
+    assert apply_record_procedure(other, &Predicate, ~"point?", ~[p]) == Bool(false);
+    assert apply_record_procedure(point, &Predicate, ~"point?", ~[Vector(@mut ~[Int(1), Int(2)])]) == Bool(false);
+    assert apply_record_procedure(point, &Accessor(0), ~"point-x", ~[Int(1)]).is_error();
+}
+
+#[test]
+fn test_that_record_procedures_check_their_arity() {
+    let point = point_type();
+    assert apply_record_procedure(point, &Constructor(~[0, 1]), ~"make-point", ~[Int(1)]).is_error();
+    assert apply_record_procedure(point, &Predicate, ~"point?", ~[]).is_error();
+}
+
+#[test]
+fn test_that_unset_fields_are_unspecified() {
+    let p = apply_record_procedure(point_type(), &Constructor(~[1]), ~"make-point", ~[Int(2)]);
+    assert p.to_str() == ~"#<record point x=#<unspecified> y=2>";
+}
+
+#[test]
+fn test_that_fields_can_hold_errors() {
+    let point = point_type();
+    let error = new_error(UserCondition, ~"Gone wrong", ~[]);
+    let p = apply_record_procedure(point, &Constructor(~[0, 1]), ~"make-point", ~[copy error, Int(2)]);
+    assert apply_record_procedure(point, &Accessor(0), ~"point-x", ~[copy p]) == error;
+    assert apply_record_procedure(point, &Modifier(1), ~"set-point-y!", ~[copy p, copy error]) == Unspecified;
+    assert apply_record_procedure(point, &Accessor(1), ~"point-y", ~[p]) == error;
+    assert apply_record_procedure(point, &Accessor(0), ~"point-x", ~[copy error]) == error;
+}
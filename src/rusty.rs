@@ -1,37 +1,65 @@
 extern mod std;
-use io::{Reader,ReaderUtil};
+use io::{Reader,ReaderUtil,WriterUtil};
 
 mod environment;
 use environment::Environment;
 mod expression;
 use expression::Expression; 
-use expression::{Bool,Int,Float,Symbol,List,Proc,Error,Lambda};
-use expression::Expression::new_proc;
+use expression::{Bool,Int,Big,Rational,Float,Complex,Symbol,String,Char,Nil,Unspecified,Pair,Vector,Bytevector,HashTable,Record,Proc,Error,Lambda,RecordProcedure};
+use expression::Cons;
+use expression::Expression::{new_proc,new_pair,new_list,new_dotted,new_error};
+mod bigint;
+use bigint::BigInt;
+mod fraction;
+use fraction::{Fraction,simplest_between};
+mod table;
+use table::{Table,Equal,Eqv,StringEquals};
+mod condition;
+use condition::{Condition,ConditionKind,SyntaxCondition,TypeCondition,ArityCondition,UnboundCondition,FileCondition,ReadCondition,UserCondition};
+mod record;
+use record::{RecordType,RecordOperation,Constructor,Predicate,Accessor,Modifier,apply_record_procedure};
 mod parse;
-use parse::parse;
+use parse::{parse,parse_all,ParseError,IncrementalReader,Datum,NeedMoreInput,SyntaxError};
+mod pretty;
+mod cst;
+mod format;
 
 fn test_env() -> @Environment {
     @Environment::new_global_environment()
 }
 
+fn test_parse( program:&str ) -> Expression {
+    match parse( program ) {
+        Ok(expression) => expression,
+        Err(error) => fail fmt!("Could not parse %s: %s", program, error.to_str())
+    }
+}
+
+fn test_parse_all( program:&str ) -> ~[Expression] {
+    match parse_all( program ) {
+        Ok(expressions) => expressions,
+        Err(error) => fail fmt!("Could not parse %s: %s", program, error.to_str())
+    }
+}
+
 fn test_eval( expr:&str, result:&str ) {
-    let evaluated = eval(parse(expr), test_env());
-    let expected = parse(result);
+    let evaluated = eval(test_parse(expr), test_env());
+    let expected = test_parse(result);
     if expected != evaluated.first() {
         fail fmt!("Expected: %s -> Got %s", expected.to_str(), evaluated.first().to_str())
     }
 }
 
 fn test_eval_fails( expr:&str, result:&str, reason:&str ) {
-    let evaluated = eval(parse(expr), test_env());
-    let expected = parse(result);
+    let evaluated = eval(test_parse(expr), test_env());
+    let expected = test_parse(result);
     if expected == evaluated.first() {
         fail fmt!("%s should not have evaluated to %s (%s)", expr, result, reason)
     }
 }
 
 fn test_eval_to_error( expr:&str, reason:&str ) {
-    let evaluated = eval(parse(expr), test_env());
+    let evaluated = eval(test_parse(expr), test_env());
     match evaluated.first() {
         Error(_) => (),
         _ => fail fmt!("%s should have returned an error: %s", expr, reason)
@@ -58,6 +86,65 @@ fn test_eval_returns_expression_when_passed_quote() {
     test_eval( ~"(quote (a))", ~"(a)" );
 }
 
+#[test]
+fn test_eval_returns_expression_when_passed_quote_shorthand() {
+    test_eval( ~"'(a b)", ~"(a b)" );
+    test_eval( ~"'a", ~"a" );
+}
+
+#[test]
+fn test_quasiquote_without_unquotes_acts_like_quote() {
+    test_eval( ~"`(a (b c))", ~"(a (b c))" );
+    test_eval( ~"`a", ~"a" );
+}
+
+#[test]
+fn test_quasiquote_evaluates_unquoted_expressions() {
+    test_eval( ~"`(1 ,(+ 1 1) 3)", ~"(1 2 3)" );
+    test_eval( ~"`,(+ 1 1)", ~"2" );
+}
+
+#[test]
+fn test_quasiquote_splices_into_the_middle_of_lists() {
+    test_eval( ~"`(1 ,@(list 2 3) 4)", ~"(1 2 3 4)" );
+    test_eval( ~"`(,@(list) 1)", ~"(1)" );
+    test_eval( ~"`(1 (,@(list 2 3)))", ~"(1 (2 3))" );
+}
+
+#[test]
+fn test_nested_quasiquote_only_evaluates_the_outermost_level() {
+    test_eval( ~"`(a `(b ,(c ,(+ 1 2))))", ~"(a `(b ,(c 3)))" );
+    test_eval( ~"`(a `(b ,(+ 1 2)))", ~"(a `(b ,(+ 1 2)))" );
+}
+
+#[test]
+fn test_quasiquote_builds_dotted_lists() {
+    test_eval( ~"`(1 . ,(+ 1 1))", ~"(1 . 2)" );
+    test_eval( ~"`(1 ,(+ 1 1) . 3)", ~"(1 2 . 3)" );
+    test_eval( ~"`(1 . ,(list 2 3))", ~"(1 2 3)" );
+}
+
+#[test]
+fn test_quasiquote_fills_in_vectors() {
+    test_eval( ~"`#(1 ,(+ 1 1) ,@(list 3 4))", ~"#(1 2 3 4)" );
+}
+
+#[test]
+fn test_that_vectors_evaluate_to_themselves() {
+    test_eval( ~"#(1 (+ 1 1) a)", ~"#(1 (+ 1 1) a)" );
+}
+
+#[test]
+fn test_that_improper_lists_cannot_be_evaluated() {
+    test_eval_to_error( ~"(+ 1 . 2)", ~"evaluated an improper list" );
+}
+
+#[test]
+fn test_unquote_splicing_requires_a_list() {
+    test_eval_to_error( ~"`(1 ,@2)", ~"spliced a number" );
+    test_eval_to_error( ~"`,@(list 1)", ~"spliced outside of a list" );
+}
+
 #[test]
 fn test_eval_returns_last_expression_when_passed_begin() {
     test_eval( ~"(begin 1 2 3)", ~"3" );
@@ -87,6 +174,22 @@ fn test_that_if_evaluates_the_else_branch() {
     test_eval( expression, ~"2");
 }
 
+#[test]
+fn test_that_if_without_an_else_branch_is_unspecified_when_false() {
+    test_eval( ~"(if 1 2)", ~"2" );
+    match eval( test_parse( ~"(if 0 2)" ), test_env() ).first() {
+        Unspecified => (),
+        value => fail fmt!("Expected nothing in particular got %s", value.to_str())
+    }
+    test_eval_to_error( ~"(if 1)", ~"if without branches" );
+}
+
+#[test]
+fn test_that_if_passes_on_an_error_in_the_test() {
+    test_eval_to_error( ~"(if (car '()) 'yes 'no)", ~"if took the error as true" );
+    test_eval_to_error( ~"(if (car '()) 'yes)", ~"one-armed if took the error as true" );
+}
+
 #[test]
 fn test_that_if_evaluates_the_test() {
     let expression = ~"(if (begin 1 0) 1 2)";
@@ -98,7 +201,7 @@ fn test_that_if_evaluates_the_test() {
 fn test_that_bare_symbol_is_interpreted_as_variable() {
     let env = test_env();
     env.define(~"monkey", Int(10));
-    let expression = parse( ~"monkey" );
+    let expression = test_parse( ~"monkey" );
     let value = eval( expression, env );
     match value {
         (Int(10), _) => (),
@@ -115,7 +218,7 @@ fn test_that_undefined_symbol_is_an_error() {
 #[test]
 fn test_that_define_can_add_a_variable() {
     let env = test_env();
-    let expression = parse( ~"(define x 10)" );
+    let expression = test_parse( ~"(define x 10)" );
     let value = eval( expression, env );
     match env.lookup(~"x") {
         Some(Int(10)) => (),
@@ -126,7 +229,7 @@ fn test_that_define_can_add_a_variable() {
 #[test]
 fn test_that_set_cannot_create_a_variable() {
     let env = test_env();
-    let expression = parse( ~"(set! x 10)" );
+    let expression = test_parse( ~"(set! x 10)" );
     let value = eval( expression, env );
     match value.first() {
         Error(_) => (),
@@ -138,7 +241,7 @@ fn test_that_set_cannot_create_a_variable() {
 fn test_that_set_can_change_a_variable() {
     let env = test_env();
     env.define(~"x", Int(100));
-    let expression = parse( ~"(set! x 10)" );
+    let expression = test_parse( ~"(set! x 10)" );
     let value = eval( expression, env );
     match env.lookup(~"x") {
         Some(Int(10)) => (),
@@ -147,22 +250,45 @@ fn test_that_set_can_change_a_variable() {
 }
 
 #[test]
-fn test_that_set_returns_the_value_not_the_key() {
+fn test_that_set_returns_the_unspecified_value() {
     let env = test_env();
     env.define(~"x", Int(100));
-    let expression = parse( ~"(set! x 10)" );
+    let expression = test_parse( ~"(set! x 10)" );
     let value = eval( expression, env );
     match value {
-        (Int(10), _) => (),
-        (Symbol(~"x"), _) => fail ~"set! returned the key, not the value",
-        _ => fail fmt!("Expected 10 got %s", value.first().to_str())
+        (Unspecified, _) => (),
+        (Symbol(~"x"), _) => fail ~"set! returned the key",
+        _ => fail fmt!("Expected nothing in particular got %s", value.first().to_str())
+    }
+}
+
+#[test]
+fn test_that_define_returns_the_unspecified_value() {
+    let env = test_env();
+    match eval( test_parse( ~"(define x 10)" ), env ).first() {
+        Unspecified => (),
+        value => fail fmt!("Expected nothing in particular got %s", value.to_str())
+    }
+    match eval( test_parse( ~"(vector-set! (vector 1) 0 2)" ), env ).first() {
+        Unspecified => (),
+        value => fail fmt!("Expected nothing in particular got %s", value.to_str())
     }
 }
 
+#[test]
+fn test_that_define_and_set_bind_errors_like_other_values() {
+    let env = test_env();
+    assert eval( test_parse( ~"(define x (car 1))" ), env ).first() == Unspecified;
+    assert env.lookup(~"x").get().is_error();
+    env.define(~"y", Int(1));
+    assert eval( test_parse( ~"(set! y (car 1))" ), env ).first() == Unspecified;
+    assert env.lookup(~"y").get().is_error();
+}
+
 #[test]
 fn test_that_begin_can_handle_one_argument() {
     let env = test_env();
-    let expression = parse( ~"(begin 10)" );
+    let expression = test_parse( ~"(begin 10)" );
     let value = eval( expression, env );
     match value {
         (Int(10), _) => (),
@@ -173,7 +299,7 @@ fn test_that_begin_can_handle_one_argument() {
 #[test]
 fn test_that_begin_evaluates_all_arguments() {
     let env = test_env();
-    let expression = parse( ~"(begin (define x 10) x)" );
+    let expression = test_parse( ~"(begin (define x 10) x)" );
     let value = eval( expression, env );
     match env.lookup(~"x") {
         Some(Int(10)) => (),
@@ -188,7 +314,7 @@ fn test_that_begin_evaluates_all_arguments() {
 #[test]
 fn test_that_other_symbols_are_evaluated_as_procs() {
     let env = test_env();
-    let expression = parse( ~"(+ 1 2)" );
+    let expression = test_parse( ~"(+ 1 2)" );
     let value = eval( expression, env );
     match value {
         (Int(3), _) => (),
@@ -199,7 +325,7 @@ fn test_that_other_symbols_are_evaluated_as_procs() {
 #[test]
 fn test_that_proc_params_are_evaluated() {
     let env=test_env();
-    let expression = parse( ~"(+ (+ 1 2) 3)" );
+    let expression = test_parse( ~"(+ (+ 1 2) 3)" );
     let value = eval (expression, env);
     match value {
         (Int(6), _) => (),
@@ -210,18 +336,27 @@ fn test_that_proc_params_are_evaluated() {
 #[test]
 fn test_that_lambda_evaluates_to_a_lambda() {
     let env=test_env();
-    let expression = parse( ~"(lambda (x) (* x x))" );
+    let expression = test_parse( ~"(lambda (x) (* x x))" );
     let value = eval(expression, env);
     match value {
-       (Lambda(_,_,_), _) => (),
+       (Lambda(_,_,_,None), _) => (),
         _ => fail ~"lambda doesn't turn into a Proc"
     }
 }
 
+#[test]
+fn test_that_procedures_print_with_their_names() {
+    let env = test_env();
+    assert eval( test_parse( ~"car" ), env ).first().to_str() == ~"#<procedure car>";
+    assert eval( test_parse( ~"(lambda (x) x)" ), env ).first().to_str() == ~"#<procedure>";
+    eval( test_parse( ~"(define square (lambda (x) (* x x)))" ), env );
+    assert eval( test_parse( ~"square" ), env ).first().to_str() == ~"#<procedure square>";
+}
+
 #[test]
 fn test_that_lambda_without_variables_evals() {
     let env=test_env();
-    let expression = parse( ~"( (lambda () (+ 1 1))  )" );
+    let expression = test_parse( ~"( (lambda () (+ 1 1))  )" );
     let value = eval(expression, env);
     match value {
         (Int(2), _) => (),
@@ -232,7 +367,7 @@ fn test_that_lambda_without_variables_evals() {
 #[test]
 fn test_that_lambda_with_a_variable_evals() {
     let env=test_env();
-    let expression = parse( ~"( (lambda (x) (+ x 1)) 1  )" );
+    let expression = test_parse( ~"( (lambda (x) (+ x 1)) 1  )" );
     let value = eval(expression, env);
     match value {
         (Int(2), _) => (),
@@ -244,10 +379,108 @@ fn eval( expression:Expression, environment:@Environment ) -> (Expression, @Envi
     fn quote(expressions:~[Expression]) -> Expression {
         match expressions {
             [_, expr] => expr,
-            _ => Error( ~"Syntax Error: quote must take a single argument" )
+            _ => new_error( SyntaxCondition, ~"quote must take a single argument", ~[] )
+        }
+    }
+
+    fn quasiquote(expressions:~[Expression], environment:@Environment) -> Expression {
+        match expressions {
+            [_, template] => quasi(template, 1, environment),
+            _ => new_error( SyntaxCondition, ~"quasiquote must take a single argument", ~[] )
+        }
+    }
+
+    // depth counts the enclosing quasiquotes; only unquotes at depth 1 are evaluated
+    fn quasi(template:Expression, depth:uint, environment:@Environment) -> Expression {
+        fn wrap(name:~str, expression:Expression) -> Expression {
+            if expression.is_error() {
+                expression
+            } else {
+                new_list(~[Symbol(name), expression])
+            }
+        }
+
+        match copy template {
+            Pair(_) => {
+                let (items, tail) = template.list_parts();
+                match (copy items, tail == Nil) {
+                    ([Symbol(~"unquote"), expr], true) => {
+                        if depth == 1 {
+                            eval(expr, environment).first()
+                        } else {
+                            wrap(~"unquote", quasi(expr, depth - 1, environment))
+                        }
+                    }
+                    ([Symbol(~"unquote-splicing"), expr], true) => {
+                        if depth == 1 {
+                            new_error( SyntaxCondition, ~"unquote-splicing must appear inside a list", ~[] )
+                        } else {
+                            wrap(~"unquote-splicing", quasi(expr, depth - 1, environment))
+                        }
+                    }
+                    ([Symbol(~"quasiquote"), expr], true) => {
+                        wrap(~"quasiquote", quasi(expr, depth + 1, environment))
+                    }
+                    _ => quasi_list(items, tail, depth, environment)
+                }
+            }
+            Vector(items) => {
+                let result = quasi_list(copy *items, Nil, depth, environment);
+                match result.list_items() {
+                    Some(items) => Vector(@mut items),
+                    None => result
+                }
+            }
+            _ => template
         }
     }
 
+    // the items filled in and chained onto the filled in tail
+    fn quasi_list(items:~[Expression], tail:Expression, depth:uint, environment:@Environment) -> Expression {
+        let mut result:~[Expression] = ~[];
+        for items.eachi() |index, &item| {
+            // `(a . ,b) reads as (a unquote b)
+            if depth == 1 && index > 0 && index + 2 == items.len() && item == Symbol(~"unquote") && tail == Nil {
+                let tail = eval(items.last(), environment).first();
+                if tail.is_error() {
+                    return tail;
+                }
+                return new_dotted(result, tail);
+            }
+            let splice = match copy item {
+                Pair(_) if depth == 1 => match item.list_items() {
+                    Some([Symbol(~"unquote-splicing"), expr]) => Some(expr),
+                    _ => None
+                },
+                _ => None
+            };
+            match splice {
+                Some(expr) => {
+                    let value = eval(expr, environment).first();
+                    if value.is_error() {
+                        return value;
+                    }
+                    match value.list_items() {
+                        Some(spliced) => result += spliced,
+                        None => return new_error( TypeCondition, ~"unquote-splicing requires a list", ~[value] )
+                    }
+                }
+                None => {
+                    let value = quasi(item, depth, environment);
+                    if value.is_error() {
+                        return value;
+                    }
+                    result.push(value);
+                }
+            }
+        }
+        let rest = quasi(tail, depth, environment);
+        if rest.is_error() {
+            return rest;
+        }
+        new_dotted(result, rest)
+    }
+
     fn begin(expressions:~[Expression], environment:@Environment) -> Expression {
         for expressions.tail().init().each() |&expression| {
             eval( expression, environment );
@@ -259,13 +492,27 @@ fn eval( expression:Expression, environment:@Environment ) -> (Expression, @Envi
         match expressions {
             [_, test, true_expr, false_expr] => {
                 let condition = eval(test, environment).first();
+                if condition.is_error() {
+                    return condition;
+                }
                 eval(if condition.to_bool() {
                     true_expr
                 } else {
                     false_expr
                 }, environment).first()
             }
-            _ => Error( ~"Syntax Error: if must take three arguments" )
+            [_, test, true_expr] => {
+                let condition = eval(test, environment).first();
+                if condition.is_error() {
+                    return condition;
+                }
+                if condition.to_bool() {
+                    eval(true_expr, environment).first()
+                } else {
+                    Unspecified
+                }
+            }
+            _ => new_error( SyntaxCondition, ~"if must take two or three arguments", ~[] )
         }
     }
 
@@ -275,12 +522,12 @@ fn eval( expression:Expression, environment:@Environment ) -> (Expression, @Envi
                 match copy symbol {
                     Symbol( key ) => {
                         environment.reset(key, eval(value, environment).first());
-                        symbol
+                        Unspecified
                     }
-                    _ => Error( fmt!("Syntax Error: %s takes a symbol as its first argument", function) )
+                    _ => new_error( SyntaxCondition, fmt!("%s takes a symbol as its first argument", function), ~[copy symbol] )
                 }
             }
-            _ => Error( fmt!("Syntax Error: %s must take two arguments", function) )
+            _ => new_error( SyntaxCondition, fmt!("%s must take two arguments", function), ~[] )
         }
     }
 
@@ -289,13 +536,17 @@ fn eval( expression:Expression, environment:@Environment ) -> (Expression, @Envi
             [_, symbol, value] => {
                 match copy symbol {
                     Symbol( key ) => {
-                        environment.define(key, eval(value, environment).first());
-                        symbol
+                        let value = match eval(value, environment).first() {
+                            Lambda(body, params, env, None) => Lambda(body, params, env, Some(copy key)),
+                            value => value
+                        };
+                        environment.define(key, value);
+                        Unspecified
                     }
-                    _ => Error( fmt!("Syntax Error: %s takes a symbol as its first argument", function) )
+                    _ => new_error( SyntaxCondition, fmt!("%s takes a symbol as its first argument", function), ~[copy symbol] )
                 }
             }
-            _ => Error( fmt!("Syntax Error: %s must take two arguments", function) )
+            _ => new_error( SyntaxCondition, fmt!("%s must take two arguments", function), ~[] )
         }
     }
 
@@ -309,7 +560,7 @@ fn eval( expression:Expression, environment:@Environment ) -> (Expression, @Envi
                 match copy symbol {
                     Symbol( key ) => {
                         match environment.lookup( copy key )  {
-                            None => return Error( ~"Syntax Error: set! cannot create a variable" ),
+                            None => return new_error( UnboundCondition, ~"set! cannot create a variable", ~[copy symbol] ),
                             _ => ()
                         }
                     }
@@ -318,65 +569,102 @@ fn eval( expression:Expression, environment:@Environment ) -> (Expression, @Envi
             }
             _ => ()
         }
-        let symbol = reset_variable(expressions, environment, ~"set!");
-        match copy symbol {
-            Symbol( key ) => {
-                match environment.lookup( copy key ) {
-                    Some( value ) => value,
-                    None => Error( ~"System Error: Reset variable failed to set the new value" )
-                }
-            }
-            _ => Error( ~"System Error: Reset variable didn't return a symbol" )
-        }
+        reset_variable(expressions, environment, ~"set!")
     }
 
     fn proc(expressions:~[Expression], environment:@Environment) -> Expression {
         let exprs = expressions.map(|&expr| eval(expr, environment).first());
-        match exprs.head() {
-            Proc( procedure, _ ) => procedure( exprs.tail() ),
-            Lambda( expr, variables, env ) => {
-                let local_env = @Environment::new( *env );
-                for vec::zip(copy variables, exprs.tail()).each |param| {
-                    match param.first() {
-                        Symbol(key) => local_env.define(key, param.second()),
-                        _ => return Error( ~"Variable names must be symbols" )
-                    }
+        apply( exprs.head(), exprs.tail() )
+    }
+
+    fn lambda(expressions:~[Expression], env:@Environment) -> Expression {
+        match copy expressions {
+            [_, params, expression] => {
+                match params.list_items() {
+                    Some(param_names) => Lambda(@expression, param_names, env, None),
+                    None => new_error( SyntaxCondition, ~"lambda requires a list of parameters", ~[params] )
                 }
-                eval( *expr, local_env).first()
             }
-            _ => Error( fmt!("\"%s\" is not a procedure", exprs.head().to_str()) )
+            _ => new_error( SyntaxCondition, fmt!("lambda requires 2 arguments, got %u", expressions.len()-1), ~[] )
         }
     }
 
-    fn lambda(expressions:~[Expression], env:@Environment) -> Expression {
-        match copy expressions {
-            [_, List(param_names), expression] => {
-                Lambda(@expression, param_names, env)
+    // (define-record-type <point> (make-point x y) point? (x point-x set-point-x!) (y point-y))
+    fn define_record_type(expressions:~[Expression], environment:@Environment) -> Expression {
+        if expressions.len() < 4 {
+            return new_error( SyntaxCondition, ~"define-record-type requires a type name, a constructor and a predicate", ~[] );
+        }
+        let type_name = match copy expressions[1] {
+            Symbol( name ) => name,
+            other => return new_error( SyntaxCondition, ~"define-record-type requires a symbol for the type name", ~[other] )
+        };
+        let mut fields:~[~str] = ~[];
+        let mut procedures:~[(~str, RecordOperation)] = ~[];
+        for vec::slice(expressions, 4, expressions.len()).each() |spec| {
+            let (field, accessor, modifier) = match spec.list_items() {
+                Some([Symbol(field), Symbol(accessor)]) => (field, accessor, None),
+                Some([Symbol(field), Symbol(accessor), Symbol(modifier)]) => (field, accessor, Some(modifier)),
+                _ => return new_error( SyntaxCondition, ~"define-record-type fields are written (field accessor) or (field accessor modifier)", ~[copy *spec] )
+            };
+            if vec::contains(fields, &field) {
+                return new_error( SyntaxCondition, ~"define-record-type has two fields with the same name", ~[Symbol(field)] );
+            }
+            procedures.push((accessor, Accessor(fields.len())));
+            match modifier {
+                Some(modifier) => procedures.push((modifier, Modifier(fields.len()))),
+                None => ()
             }
-            _ => Error( fmt!("Syntax Error: lambda requires 2 arguments, got \"%u\"", expressions.len()-1 ) )
+            fields.push(field);
         }
+        match expressions[2].list_items() {
+            Some([Symbol(constructor), ..arguments]) => {
+                let mut indices:~[uint] = ~[];
+                for arguments.each() |argument| {
+                    let index = match copy *argument {
+                        Symbol(name) => vec::position_elem(fields, &name),
+                        _ => None
+                    };
+                    match index {
+                        Some(index) => indices.push(index),
+                        None => return new_error( SyntaxCondition, ~"the define-record-type constructor takes a name that is not a field", ~[copy *argument] )
+                    }
+                }
+                procedures.push((constructor, Constructor(indices)));
+            }
+            _ => return new_error( SyntaxCondition, ~"define-record-type requires a constructor like (make-name field ...)", ~[copy expressions[2]] )
+        }
+        match copy expressions[3] {
+            Symbol( predicate ) => procedures.push((predicate, Predicate)),
+            other => return new_error( SyntaxCondition, ~"define-record-type requires a symbol for the predicate", ~[other] )
+        }
+        let record_type = @RecordType { name: copy type_name, fields: fields };
+        for procedures.each() |&(name, operation)| {
+            environment.define(copy name, RecordProcedure(record_type, operation, name));
+        }
+        Unspecified
     }
 
     (match copy expression {
-        List( expressions ) => {
-            if expressions.len() == 0 {
-                expression
-            } else {
-                match expressions[0] {
+        Pair(_) => {
+            match expression.list_items() {
+                Some(expressions) => match expressions[0] {
                     Symbol(~"quote") => quote(expressions),
+                    Symbol(~"quasiquote") => quasiquote(expressions, environment),
                     Symbol(~"begin") => begin(expressions, environment),
                     Symbol(~"if") => if_(expressions, environment),
                     Symbol(~"define") => define(expressions, environment),
                     Symbol(~"set!") => set_bang(expressions, environment),
                     Symbol(~"lambda") => lambda(expressions, environment),
+                    Symbol(~"define-record-type") => define_record_type(expressions, environment),
                     _ => proc(expressions, environment) 
-                }
+                },
+                None => new_error( SyntaxCondition, ~"cannot evaluate an improper list", ~[copy expression] )
             }
         }
         Symbol( symbol ) => {
             match environment.lookup( copy symbol ) {
                 Some( value ) => value,
-                None => Error( fmt!("Undefined symbol %s",symbol) )
+                None => new_error( UnboundCondition, ~"Undefined symbol", ~[Symbol(symbol)] )
             }
         }
         _ => {
@@ -385,21 +673,309 @@ fn eval( expression:Expression, environment:@Environment ) -> (Expression, @Envi
     }, environment)
 }
 
+#[test]
+fn test_that_define_record_type_makes_records() {
+    let point = ~"(define-record-type <point> (make-point x y) point? (x point-x set-point-x!) (y point-y))";
+    test_eval( ~"(begin " + point + ~" (point-y (make-point 1 2)))", ~"2" );
+    test_eval( ~"(begin " + point + ~" (define p (make-point 1 2)) (set-point-x! p 3) (point-x p))", ~"3" );
+    test_eval( ~"(begin " + point + ~" (list (point? (make-point 1 2)) (point? (vector 1 2)) (point? 1)))", ~"(#t #f #f)" );
+    test_eval( ~"(begin " + point + ~" (list (equal? (make-point 1 2) (make-point 1 2)) (eq? point-x point-x)))", ~"(#f #t)" );
+    test_eval_to_error( ~"(begin " + point + ~" (point-x (vector 1 2)))", ~"accessor given a vector" );
+    test_eval_to_error( ~"(begin " + point + ~" (make-point 1))", ~"constructor given too few arguments" );
+    test_eval_to_error( ~"(define-record-type point (make-point z) point? (x point-x))", ~"constructor names a missing field" );
+    test_eval_to_error( ~"(define-record-type point (make-point x) point? (x point-x) (x point-x2))", ~"duplicate field" );
+}
+
+#[test]
+fn test_that_fields_the_constructor_leaves_out_are_unspecified() {
+    let env = test_env();
+    eval( test_parse( ~"(define-record-type node (make-node value) node? (value node-value) (next node-next set-node-next!))" ), env );
+    match eval( test_parse( ~"(node-next (make-node 1))" ), env ).first() {
+        Unspecified => (),
+        value => fail fmt!("Expected an unspecified field got %s", value.to_str())
+    }
+}
+
+#[test]
+fn test_that_records_print_their_fields() {
+    let env = test_env();
+    eval( test_parse( ~"(define-record-type <point> (make-point x y) point? (x point-x) (y point-y))" ), env );
+    assert eval( test_parse( ~"(make-point 1 \"a\")" ), env ).first().to_str() == ~"#<record point x=1 y=\"a\">";
+    assert eval( test_parse( ~"point-x" ), env ).first().to_str() == ~"#<procedure point-x>";
+}
+
+#[test]
+fn test_that_apply_calls_builtins_and_lambdas() {
+    let env = test_env();
+    let square = eval( test_parse( ~"(lambda (x) (* x x))" ), env ).first();
+    let plus = eval( test_parse( ~"+" ), env ).first();
+    assert apply( square, ~[Int(3)] ) == Int(9);
+    assert apply( plus, ~[Int(1), Int(2)] ) == Int(3);
+}
+
+#[test]
+fn test_that_apply_checks_lambda_arity() {
+    let env = test_env();
+    let square = eval( test_parse( ~"(lambda (x) (* x x))" ), env ).first();
+    assert apply( copy square, ~[] ).is_error();
+    assert apply( square, ~[Int(1), Int(2)] ).is_error();
+    assert apply( Int(1), ~[] ).is_error();
+}
+
+// calls a procedure value with already evaluated arguments
+fn apply( procedure:Expression, args:~[Expression] ) -> Expression {
+    match copy procedure {
+        Proc( function, _, _ ) => function( args ),
+        RecordProcedure( record_type, operation, name ) => apply_record_procedure( record_type, &operation, name, args ),
+        Lambda( expr, variables, env, _ ) => {
+            if variables.len() != args.len() {
+                return new_error( ArityCondition, fmt!("%s expects %u argument%s, got %u", procedure.to_str(), variables.len(), if variables.len() == 1 { ~"" } else { ~"s" }, args.len()), copy args );
+            }
+            let local_env = @Environment::new( *env );
+            for vec::zip(copy variables, args).each |param| {
+                match param.first() {
+                    Symbol(key) => local_env.define(key, param.second()),
+                    _ => return new_error( SyntaxCondition, ~"Variable names must be symbols", ~[param.first()] )
+                }
+            }
+            eval( *expr, local_env).first()
+        }
+        // an operator that failed to evaluate keeps its own condition
+        Error(_) => procedure,
+        _ => new_error( TypeCondition, ~"Not a procedure", ~[procedure] )
+    }
+}
+
+#[test]
+fn test_that_eval_program_returns_the_last_value() {
+    let env = test_env();
+    match eval_program( test_parse_all( ~"(define x 1) (set! x (+ x 1)) (+ x 1)" ), env ) {
+        Int(3) => (),
+        value => fail fmt!("Expected 3 got %s", value.to_str())
+    }
+}
+
+#[test]
+fn test_that_eval_program_shares_one_environment() {
+    let env = test_env();
+    eval_program( test_parse_all( ~"(define x 1)" ), env );
+    eval_program( test_parse_all( ~"(define y (+ x 1))" ), env );
+    match env.lookup(~"y") {
+        Some(Int(2)) => (),
+        _ => fail ~"second program did not see the first program's definitions"
+    }
+}
+
+#[test]
+fn test_that_eval_program_stops_at_the_first_error() {
+    let env = test_env();
+    match eval_program( test_parse_all( ~"(define x 1) (undefined-thing) (define x 2)" ), env ) {
+        Error(_) => (),
+        value => fail fmt!("Expected an error got %s", value.to_str())
+    }
+    match env.lookup(~"x") {
+        Some(Int(1)) => (),
+        _ => fail ~"evaluation continued after an error"
+    }
+}
+
+#[test]
+fn test_that_an_empty_program_evaluates_to_the_empty_list() {
+    test_eval_program_is_empty_list( ~"" );
+    test_eval_program_is_empty_list( ~"; just a comment" );
+}
+
+fn test_eval_program_is_empty_list( program:&str ) {
+    match eval_program( test_parse_all( program ), test_env() ) {
+        Nil => (),
+        value => fail fmt!("Expected () got %s", value.to_str())
+    }
+}
+
+fn eval_program( expressions:~[Expression], environment:@Environment ) -> Expression {
+    let mut result = Nil;
+    for expressions.each() |&expression| {
+        result = eval( expression, environment ).first();
+        if result.is_error() {
+            break;
+        }
+    }
+    result
+}
+
+#[test]
+fn test_that_errors_carry_their_kind_and_irritants() {
+    match eval( test_parse( ~"undefined-thing" ), test_env() ).first() {
+        Error(condition) => {
+            match condition.kind { UnboundCondition => (), _ => fail ~"wrong kind of condition" }
+            assert condition.irritants == ~[Symbol(~"undefined-thing")];
+        }
+        value => fail fmt!("Expected an error got %s", value.to_str())
+    }
+    match eval( test_parse( ~"(undefined-fn 1)" ), test_env() ).first() {
+        Error(condition) => match condition.kind { UnboundCondition => (), _ => fail ~"the unbound operator was reported as something else" },
+        value => fail fmt!("Expected an error got %s", value.to_str())
+    }
+    match eval( test_parse( ~"((lambda (x) x))" ), test_env() ).first() {
+        Error(condition) => match condition.kind { ArityCondition => (), _ => fail ~"wrong kind of condition" },
+        value => fail fmt!("Expected an error got %s", value.to_str())
+    }
+}
+
+#[test]
+fn test_that_run_turns_unreadable_programs_into_read_conditions() {
+    match run( ~"(define x 1)\n(car '(1 2)", test_env() ) {
+        Error(condition) => {
+            assert condition.is_read_error();
+            assert condition.location == Some((2, 1));
+        }
+        value => fail fmt!("Expected a read error got %s", value.to_str())
+    }
+}
+
+// the program's value, or a read condition when it could not be parsed
+fn run( program:&str, environment:@Environment ) -> Expression {
+    match parse_all( program ) {
+        Ok(expressions) => eval_program( expressions, environment ),
+        Err(error) => Error(@Condition::from_parse_error(&error))
+    }
+}
+
+// the width of the terminal as the shell reports it, for laying out long results
+fn terminal_width() -> uint {
+    match os::getenv(~"COLUMNS") {
+        Some(columns) => match uint::from_str(columns) {
+            Some(width) if width > 0 => width,
+            _ => pretty::default_width
+        },
+        None => pretty::default_width
+    }
+}
+
+// formats one file in place, or with check only reports whether it would change;
+// false if the file was unreadable, unwritable or not formatted
+fn format_file( file:&str, check:bool ) -> bool {
+    let program = match io::read_whole_file_str(&Path(file)) {
+        Ok(program) => program,
+        Err(message) => {
+            io::stderr().write_line( fmt!("%s: %s", file, message) );
+            return false;
+        }
+    };
+    match format::format_source( program, pretty::default_width ) {
+        Ok(formatted) => {
+            if formatted == program {
+                true
+            } else if check {
+                io::stderr().write_line( fmt!("%s is not formatted", file) );
+                false
+            } else {
+                match io::file_writer(&Path(file), [io::Create, io::Truncate]) {
+                    Ok(writer) => {
+                        writer.write_str(formatted);
+                        true
+                    }
+                    Err(message) => {
+                        io::stderr().write_line( fmt!("%s: %s", file, message) );
+                        false
+                    }
+                }
+            }
+        }
+        Err(error) => {
+            io::stderr().write_line( fmt!("%s:%u:%u: %s", file, error.line, error.column, error.message()) );
+            false
+        }
+    }
+}
+
+// rusty fmt [--check] file...
+fn format_files( args:~[~str] ) {
+    let check = vec::contains(args, &~"--check");
+    let files = args.filtered(|arg| *arg != ~"--check");
+    if files.len() == 0 {
+        io::stderr().write_line("usage: rusty fmt [--check] file...");
+        os::set_exit_status(2);
+        return;
+    }
+    let mut ok = true;
+    for files.each() |&file| {
+        ok = format_file( file, check ) && ok;
+    }
+    if !ok {
+        os::set_exit_status(1);
+    }
+}
+
 fn main() {
-    fn evaluate( expr:~str, env:Environment ) -> Option<Environment> {
-        let (result, new_env) = eval( parse(expr), @env );
-        io::println( fmt!("%s -> %s", expr, result.to_str() ));
-        Some(*new_env)
+    let env = @Environment::new_global_environment();
+    let args = os::args().tail();
+    if args.len() > 0 && args[0] == ~"fmt" {
+        format_files( args.tail() );
+        return;
+    }
+    let files = args;
+    if files.len() > 0 {
+        for files.each() |&file| {
+            let result = match io::read_whole_file_str(&Path(file)) {
+                Ok(program) => run( program, env ),
+                Err(message) => new_error( FileCondition, message, ~[String(copy file)] )
+            };
+            match copy result {
+                Error(condition) => {
+                    match (condition.kind, condition.location) {
+                        (_, Some((line, column))) => io::println( fmt!("%s:%u:%u: %s", file, line, column, condition.message) ),
+                        // the file is already among the irritants
+                        (FileCondition, None) => io::println( result.to_str() ),
+                        _ => io::println( fmt!("%s: %s", file, result.to_str()) )
+                    }
+                    return;
+                }
+                _ => ()
+            }
+        }
+        return;
     }
 
-    let mut env = Environment::new_global_environment();
+    let reader = IncrementalReader::new();
+    let stdin = io::stdin();
+    let width = terminal_width();
     loop {
-        io::print("rusty> ");
-        let in = io::stdin().read_line();
-        let result = evaluate( in, copy env );
-        match result {
-            Some(new_env) => env = new_env,
-            None => ()
+        io::print(if reader.in_progress() { "   ...> " } else { "rusty> " });
+        let line = stdin.read_line();
+        if stdin.eof() {
+            // the last line may have no newline after it
+            if line.len() > 0 {
+                reader.feed(line);
+            }
+            reader.close();
+        } else {
+            reader.feed(line + ~"\n");
+        }
+        loop {
+            match reader.next() {
+                Datum(expression) => {
+                    let result = eval( copy expression, env ).first();
+                    // definitions and mutations have nothing to show
+                    if result != Unspecified {
+                        let line = fmt!("%s -> %s", expression.to_str(), result.to_str());
+                        if str::char_len(line) <= width {
+                            io::println( line );
+                        } else {
+                            io::println( pretty::pretty(&expression, width) + ~" ->" );
+                            io::println( pretty::pretty(&result, width) );
+                        }
+                    }
+                }
+                NeedMoreInput => break,
+                SyntaxError(error) => {
+                    io::println( fmt!("Syntax Error: %s", error.to_str()) );
+                }
+            }
+        }
+        if stdin.eof() {
+            io::println("");
+            break;
         }
     }
 }
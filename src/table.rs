@@ -0,0 +1,222 @@
+/*
+ * Mutable hash tables, keyed by equal?, eqv? or string=?
+ *
+ * Keys are hashed in a way that agrees with the table's equivalence, so keys
+ * that it treats as the same always land in the same bucket. As with any
+ * hashed key, a pair or vector used as a key in an equal? table should not be
+ * changed while it is in the table.
+ */
+
+use expression::cell_address;
+
+pub enum Equivalence {
+    Equal,
+    Eqv,
+    StringEquals
+}
+
+pub struct Table {
+    equivalence: Equivalence,
+    // entries in lists chosen by the hash of their key
+    buckets: ~[~[(Expression, Expression)]],
+    size: uint
+}
+
+// how far into pairs and vectors an equal? hash looks, which keeps it finite for
+// cyclic keys; keys that only differ further in share a bucket
+const hash_depth:uint = 4;
+
+// FNV-1a, one value at a time
+fn mix( hash:uint, value:uint ) -> uint {
+    (hash ^ value) * 16777619
+}
+
+fn hash_text( text:&str ) -> uint {
+    let mut hash = 2166136261u;
+    for str::each(text) |byte| {
+        hash = mix(hash, byte as uint);
+    }
+    hash
+}
+
+// a hash of the key's contents, which keys that are equal? share
+fn hash_equal( key:&Expression, depth:uint ) -> uint {
+    match copy *key {
+        Pair(cell) => {
+            if depth == 0 {
+                return 1;
+            }
+            mix(mix(2, hash_equal(&cell.car, depth - 1)), hash_equal(&cell.cdr, depth - 1))
+        }
+        Vector(items) => {
+            let mut hash = mix(3, items.len());
+            if depth > 0 {
+                for vec::slice(*items, 0, uint::min(items.len(), 4)).each() |item| {
+                    hash = mix(hash, hash_equal(item, depth - 1));
+                }
+            }
+            hash
+        }
+        HashTable(table) => unsafe { cast::reinterpret_cast(&table) },
+        Record(_, values) => unsafe { cast::reinterpret_cast(&values) },
+        // 0.0 and -0.0 are equal but written differently
+        Float(number) if number == 0.0 => hash_text("0.0"),
+        // procedures compare by where their code is, which the written form does not show
+        Proc(_, _, _) | Lambda(_, _, _, _) => 0,
+        _ => hash_text(key.to_str())
+    }
+}
+
+// a hash for eqv?, which tells pairs, vectors, bytevectors and records apart by where they live
+fn hash_eqv( key:&Expression ) -> uint {
+    match cell_address(key) {
+        Some(address) => address,
+        None => hash_equal(key, 0)
+    }
+}
+
+pub impl Table {
+    static fn new( equivalence:Equivalence ) -> Table {
+        Table { equivalence: equivalence, buckets: vec::from_fn(8, |_| ~[]), size: 0 }
+    }
+
+    // whether the table's equivalence can compare the key at all
+    pure fn accepts( &self, key:&Expression ) -> bool {
+        match self.equivalence {
+            StringEquals => match *key { String(_) => true, _ => false },
+            Equal | Eqv => true
+        }
+    }
+
+    pure fn same( &self, a:&Expression, b:&Expression ) -> bool {
+        match self.equivalence {
+            Eqv => a.is_eqv(b),
+            Equal | StringEquals => *a == *b
+        }
+    }
+
+    fn bucket( &self, key:&Expression ) -> uint {
+        let hash = match self.equivalence {
+            Eqv => hash_eqv(key),
+            Equal | StringEquals => hash_equal(key, hash_depth)
+        };
+        hash % self.buckets.len()
+    }
+
+    fn find( &self, key:&Expression ) -> Option<Expression> {
+        for self.buckets[self.bucket(key)].each() |entry| {
+            if self.same(&entry.first(), key) {
+                return Some(entry.second());
+            }
+        }
+        None
+    }
+
+    // replaces the value of a key already in the table
+    fn insert( &mut self, key:Expression, value:Expression ) {
+        let index = self.bucket(&key);
+        match vec::position(self.buckets[index], |entry| self.same(&entry.first(), &key)) {
+            Some(i) => self.buckets[index][i] = (key, value),
+            None => {
+                self.buckets[index].push((key, value));
+                self.size += 1;
+                if self.size > 2 * self.buckets.len() {
+                    self.grow();
+                }
+            }
+        }
+    }
+
+    // false when the key was not in the table
+    fn remove( &mut self, key:&Expression ) -> bool {
+        let index = self.bucket(key);
+        match vec::position(self.buckets[index], |entry| self.same(&entry.first(), key)) {
+            Some(i) => {
+                vec::swap_remove(&mut self.buckets[index], i);
+                self.size -= 1;
+                true
+            }
+            None => false
+        }
+    }
+
+    // every key and value, in no particular order
+    pure fn entries( &self ) -> ~[(Expression, Expression)] {
+        vec::concat(self.buckets)
+    }
+
+    // doubles the number of buckets, so that they stay short
+    fn grow( &mut self ) {
+        let entries = self.entries();
+        self.buckets = vec::from_fn(self.buckets.len() * 2, |_| ~[]);
+        for entries.each() |entry| {
+            let index = self.bucket(&entry.first());
+            self.buckets[index].push(copy *entry);
+        }
+    }
+}
+
+#[test]
+fn test_that_equal_tables_find_keys_by_contents() {
+    let mut table = Table::new(Equal);
+    table.insert(Expression::new_list(~[Int(1), String(~"a")]), Int(1));
+    assert table.find(&Expression::new_list(~[Int(1), String(~"a")])) == Some(Int(1));
+    assert table.find(&Expression::new_list(~[Int(1), String(~"b")])).is_none();
+    table.insert(Float(-0.0), Int(2));
+    assert table.find(&Float(0.0)) == Some(Int(2));
+    assert table.find(&Int(0)).is_none();
+}
+
+#[test]
+fn test_that_eqv_tables_find_pairs_by_identity() {
+    let mut table = Table::new(Eqv);
+    let key = Expression::new_list(~[Int(1)]);
+    table.insert(copy key, Int(1));
+    assert table.find(&key) == Some(Int(1));
+    assert table.find(&Expression::new_list(~[Int(1)])).is_none();
+    table.insert(String(~"a"), Int(2));
+    assert table.find(&String(~"a")) == Some(Int(2));
+}
+
+#[test]
+fn test_that_string_tables_only_accept_strings() {
+    let table = Table::new(StringEquals);
+    assert table.accepts(&String(~"a"));
+    assert !table.accepts(&Symbol(~"a"));
+}
+
+#[test]
+fn test_that_inserting_a_key_again_replaces_its_value() {
+    let mut table = Table::new(Equal);
+    table.insert(Symbol(~"a"), Int(1));
+    table.insert(Symbol(~"a"), Int(2));
+    assert table.size == 1;
+    assert table.find(&Symbol(~"a")) == Some(Int(2));
+    assert table.remove(&Symbol(~"a"));
+    assert !table.remove(&Symbol(~"a"));
+    assert table.size == 0;
+}
+
+#[test]
+fn test_that_tables_grow_and_keep_their_entries() {
+    let mut table = Table::new(Equal);
+    for int::range(0, 1000) |i| {
+        table.insert(Int(i), Int(i * i));
+    }
+    assert table.size == 1000 && table.buckets.len() > 8;
+    for int::range(0, 1000) |i| {
+        assert table.find(&Int(i)) == Some(Int(i * i));
+    }
+    assert table.entries().len() == 1000;
+}
+
+#[test]
+fn test_that_cyclic_keys_can_be_hashed() {
+    let cell = @mut Cons { car: Int(1), cdr: Nil };
+    cell.cdr = Pair(cell);
+    let mut table = Table::new(Equal);
+    table.insert(Pair(cell), Int(1));
+    table.insert(Pair(cell), Int(2));
+    assert table.size == 1;
+    assert table.find(&Pair(cell)) == Some(Int(2));
+}